@@ -0,0 +1,123 @@
+//! Derive macros for polestar.
+
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields};
+
+/// Derive `polestar::id::Relabel<I>` for every [`Id`](polestar::id::Id) type `I`,
+/// by relabeling each field in turn. Each type parameter must also implement `Relabel`.
+///
+/// Fields marked `#[relabel(skip)]` are cloned unchanged, and need not implement `Relabel`.
+#[proc_macro_derive(Relabel, attributes(relabel))]
+pub fn derive_relabel(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match relabel(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn relabel(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let id = format_ident!("__RelabelId");
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, expr) = relabel_fields(&data.fields)?;
+            quote! {
+                let #name #pattern = self;
+                #name #expr
+            }
+        }
+        Data::Enum(data) => {
+            let arms = data
+                .variants
+                .iter()
+                .map(|variant| {
+                    let (pattern, expr) = relabel_fields(&variant.fields)?;
+                    let variant = &variant.ident;
+                    Ok(quote! { #name::#variant #pattern => #name::#variant #expr, })
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Relabel cannot be derived for unions",
+            ))
+        }
+    };
+
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let mut generics = input.generics.clone();
+    generics.params.push(parse_quote!(#id: ::polestar::id::Id));
+    let params = input
+        .generics
+        .type_params()
+        .map(|p| &p.ident)
+        .collect::<Vec<_>>();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: ::polestar::id::Relabel<#id>));
+    }
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::polestar::id::Relabel<#id> for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn relabel(&self, f: &impl Fn(#id) -> #id) -> Self {
+                #body
+            }
+        }
+    })
+}
+
+/// Return a pattern binding each of the fields, and an expression which rebuilds them
+/// relabeled.
+fn relabel_fields(
+    fields: &Fields,
+) -> syn::Result<(proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    let mut bindings = vec![];
+    let mut values = vec![];
+    for (i, field) in fields.iter().enumerate() {
+        let binding = format_ident!("__field{}", i);
+        let mut skip = false;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("relabel")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    skip = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `skip`"))
+                }
+            })?;
+        }
+        values.push(if skip {
+            quote! { ::std::clone::Clone::clone(#binding) }
+        } else {
+            quote! { ::polestar::id::Relabel::relabel(#binding, f) }
+        });
+        bindings.push(binding);
+    }
+    Ok(match fields {
+        Fields::Named(_) => {
+            let names = fields
+                .iter()
+                .map(|f| f.ident.as_ref().unwrap())
+                .collect::<Vec<_>>();
+            (
+                quote! { { #(#names: #bindings),* } },
+                quote! { { #(#names: #values),* } },
+            )
+        }
+        Fields::Unnamed(_) => (quote! { ( #(#bindings),* ) }, quote! { ( #(#values),* ) }),
+        Fields::Unit => (quote! {}, quote! {}),
+    })
+}
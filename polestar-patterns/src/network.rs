@@ -69,6 +69,7 @@ impl<N: Node> polestar::Machine for Model<N> {
 
 impl<N: Node> Model<N> {
     /// Transition a node and trigger any follow-on actions due to effects.
+    #[allow(clippy::type_complexity)]
     pub fn transition_recursively(
         &self,
         mut state: State<N>,
@@ -9,18 +9,14 @@
 //! The problem we're solving is, how can you reach a target number of emojis
 //! with the minimal effort?
 //!
-//! "Effort" is defined in terms of a cost function [`SpamState::cost`], where each Action has a cost.
+//! "Effort" is defined in terms of a cost function [`SpamAction::cost`], where each Action has a cost.
 //! - Typing a single character is the lowest cost action.
 //! - Pasting the clipboard is slightly more laborious.
 //! - "Select All" + "Paste" is the most laborious action.
 
 use anyhow::bail;
 use exhaustive::Exhaustive;
-use polestar::{
-    machine::store_path::{StorePathMachine, StorePathState},
-    prelude::*,
-    traversal::Traversal,
-};
+use polestar::{prelude::*, traversal::Traversal};
 
 fn main() {
     // tracing_subscriber::fmt::fmt()
@@ -29,36 +25,23 @@ fn main() {
 
     let target = 1_000;
 
-    let machine = StorePathMachine::from(SpamMachine { target });
-    let initial = StorePathState::new(SpamState::default());
-    let terminals = Traversal::new(machine, [initial])
-        .max_depth(50)
-        .run_terminal();
+    let mut solutions = Traversal::new(SpamMachine { target }, [SpamState::default()])
+        .search_min_cost(|_, action, _| action.cost(), |s| s.len >= target, |_| 0)
+        .unwrap();
+    solutions.sort_by_key(|s| s.goal().len);
 
-    let terminals = terminals.unwrap();
-    let mut states: Vec<_> = terminals.into_iter().filter(|s| s.len >= target).collect();
-    states.sort_by_key(|s| s.cost);
-    let min_cost = states[0].cost;
-
-    let mut solutions: Vec<_> = states
-        .into_iter()
-        .take_while(|s| s.cost <= min_cost + 3)
-        .take(10)
-        .collect();
-    solutions.sort_by_key(|s| (s.cost, s.len));
-
-    for (i, state) in solutions.into_iter().enumerate() {
-        let path = state
+    for (i, solution) in solutions.into_iter().enumerate() {
+        let path = solution
             .path
             .iter()
-            .map(|a| a.to_string())
+            .map(|(a, _)| a.to_string())
             .collect::<Vec<_>>()
             .join("");
         println!(
             "#{:<2} : len={:<6} cost={:<3} path={}",
             i + 1,
-            state.len,
-            state.cost,
+            solution.goal().len,
+            solution.cost,
             path
         );
     }
@@ -105,7 +88,6 @@ impl Machine for SpamMachine {
                 *s.pastes.as_mut().unwrap() += 1
             }
         }
-        s.cost += s.cost(&a);
         Ok((s, ()))
     }
 }
@@ -130,17 +112,15 @@ enum SpamAction {
 struct SpamState {
     /// The number of emojis currently onscreen.
     len: usize,
-    /// The cost of the actions taken so far.
-    cost: usize,
     /// The paste buffer length.
     buf: Option<usize>,
     /// The number of times the buffer has been pasted since the last copy.
     pastes: Option<usize>,
 }
 
-impl SpamState {
-    fn cost(&self, action: &SpamAction) -> usize {
-        match action {
+impl SpamAction {
+    fn cost(&self) -> usize {
+        match self {
             SpamAction::One => 1,
             SpamAction::Copy => 5,
             SpamAction::Paste => 3,
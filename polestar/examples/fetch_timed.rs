@@ -24,7 +24,7 @@ use polestar::{
     prelude::*,
     time::{RealTime, TickBuffer},
 };
-use rand::{Rng, RngExt};
+use rand::RngExt;
 use tokio::{sync::Mutex, task::JoinSet, time::Instant};
 
 /*                          ███
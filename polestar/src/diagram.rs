@@ -1,9 +1,15 @@
-//! Functions for generating DOT representations of state graphs.
+//! Functions for exporting state graphs in various formats, and loading them back.
 
-use petgraph::graph::DiGraph;
+use std::fmt::Write;
+
+use petgraph::{
+    graph::{DiGraph, NodeIndex},
+    visit::EdgeRef,
+};
 
 use crate::prelude::*;
 
+pub mod bisimulation;
 #[deprecated = "use traversal with graphing enabled instead"]
 #[allow(missing_docs)]
 pub mod exhaustive;
@@ -43,6 +49,265 @@ where
     dot.replace("digraph {", "digraph {\n    bgcolor=\"#131313\" ")
 }
 
+/// Get a JSON representation of a graph, listing the nodes, then the edges,
+/// each by the indices of the nodes it connects.
+/// The graph can be loaded back with [`from_json`].
+///
+/// ```json
+/// {
+///   "nodes": [state, ...],
+///   "edges": [{ "source": 0, "target": 1, "action": action }, ...]
+/// }
+/// ```
+#[cfg(feature = "recording")]
+pub fn to_json<N, E>(graph: &DiGraph<N, E>) -> serde_json::Result<String>
+where
+    N: serde::Serialize,
+    E: serde::Serialize,
+{
+    let json = JsonGraph {
+        nodes: graph.node_weights().collect(),
+        edges: graph
+            .edge_references()
+            .map(|edge| JsonEdge {
+                source: edge.source().index(),
+                target: edge.target().index(),
+                action: edge.weight(),
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&json)
+}
+
+/// Load a graph from its JSON representation (see [`to_json`]).
+/// The nodes and edges keep their indices.
+#[cfg(feature = "recording")]
+pub fn from_json<N, E>(json: &str) -> serde_json::Result<DiGraph<N, E>>
+where
+    N: serde::de::DeserializeOwned,
+    E: serde::de::DeserializeOwned,
+{
+    use petgraph::graph::NodeIndex;
+    use serde::de::Error;
+
+    let json: JsonGraph<N, E> = serde_json::from_str(json)?;
+    let mut graph = DiGraph::with_capacity(json.nodes.len(), json.edges.len());
+    for node in json.nodes {
+        graph.add_node(node);
+    }
+    for edge in json.edges {
+        let nodes = graph.node_count();
+        if edge.source >= nodes || edge.target >= nodes {
+            return Err(serde_json::Error::custom(format!(
+                "edge from {} to {} refers to a missing node, out of {nodes}",
+                edge.source, edge.target
+            )));
+        }
+        graph.add_edge(
+            NodeIndex::new(edge.source),
+            NodeIndex::new(edge.target),
+            edge.action,
+        );
+    }
+    Ok(graph)
+}
+
+#[cfg(feature = "recording")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonGraph<N, E> {
+    nodes: Vec<N>,
+    edges: Vec<JsonEdge<E>>,
+}
+
+#[cfg(feature = "recording")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonEdge<E> {
+    source: usize,
+    target: usize,
+    action: E,
+}
+
+/// Get a GraphML representation of a graph, with the `Display` representation
+/// of each state and action as the label of its node or edge,
+/// for tools like yEd, Gephi and NetworkX.
+pub fn to_graphml<N, E>(graph: &DiGraph<N, E>) -> String
+where
+    N: core::fmt::Display,
+    E: core::fmt::Display,
+{
+    let escape = |label: String| {
+        label
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    xml.push_str("  <key id=\"state\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n");
+    xml.push_str("  <key id=\"action\" for=\"edge\" attr.name=\"label\" attr.type=\"string\"/>\n");
+    xml.push_str("  <graph id=\"G\" edgedefault=\"directed\">\n");
+    for ix in graph.node_indices() {
+        let label = escape(graph[ix].to_string());
+        writeln!(
+            xml,
+            "    <node id=\"n{}\"><data key=\"state\">{label}</data></node>",
+            ix.index()
+        )
+        .unwrap();
+    }
+    for edge in graph.edge_references() {
+        let label = escape(edge.weight().to_string());
+        writeln!(
+            xml,
+            "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\"><data key=\"action\">{label}</data></edge>",
+            edge.id().index(),
+            edge.source().index(),
+            edge.target().index()
+        )
+        .unwrap();
+    }
+    xml.push_str("  </graph>\n</graphml>\n");
+    xml
+}
+
+/// Get a Mermaid state diagram of a graph, with the `Display` representation
+/// of each state and action as the label of its node or edge,
+/// which renders in Markdown on GitHub, among other places.
+pub fn to_mermaid<N, E>(graph: &DiGraph<N, E>) -> String
+where
+    N: core::fmt::Display,
+    E: core::fmt::Display,
+{
+    // Labels run to the end of the line, and may not contain the characters Mermaid
+    // uses for its own syntax, so those are written as entity codes
+    let escape = |label: String| {
+        label
+            .chars()
+            .map(|c| match c {
+                '#' => "#35;".to_string(),
+                ';' => "#59;".to_string(),
+                ':' => "#58;".to_string(),
+                '\n' => "<br/>".to_string(),
+                c => c.to_string(),
+            })
+            .collect::<String>()
+    };
+
+    let mut mermaid = String::from("stateDiagram-v2\n");
+    for ix in graph.node_indices() {
+        let label = escape(graph[ix].to_string());
+        writeln!(mermaid, "    s{} : {label}", ix.index()).unwrap();
+    }
+    for edge in graph.edge_references() {
+        let label = escape(edge.weight().to_string());
+        writeln!(
+            mermaid,
+            "    s{} --> s{} : {label}",
+            edge.source().index(),
+            edge.target().index()
+        )
+        .unwrap();
+    }
+    mermaid
+}
+
+/// Write a self-contained HTML page for exploring a graph to a file (see [`to_html`])
+pub fn write_html<N, E>(filename: &str, graph: &DiGraph<N, E>)
+where
+    N: core::fmt::Display + core::fmt::Debug,
+    E: core::fmt::Display,
+{
+    write_html_with_path(filename, graph, &[]);
+}
+
+/// Write a self-contained HTML page for exploring a graph to a file, with a path through
+/// it highlighted, like a counterexample from the model checker (see [`to_html`])
+pub fn write_html_with_path<N, E>(filename: &str, graph: &DiGraph<N, E>, path: &[NodeIndex])
+where
+    N: core::fmt::Display + core::fmt::Debug,
+    E: core::fmt::Display,
+{
+    std::fs::write(filename, to_html(graph, path)).unwrap();
+}
+
+/// Get a single HTML page for exploring a graph in a browser, which embeds the graph
+/// and needs nothing else, not even a network connection. This is usable for graphs
+/// far too big to lay out with Graphviz.
+///
+/// States can be searched for by the text of their `Display` or `Debug` representations,
+/// both of which are shown for the state selected, along with the states before and
+/// after it, whose successors can be expanded in turn. The nodes of `path` are
+/// highlighted, and can be stepped through in order, even where the path visits
+/// a node more than once.
+///
+/// # Panics
+///
+/// Panics if a node of the path is not in the graph.
+pub fn to_html<N, E>(graph: &DiGraph<N, E>, path: &[NodeIndex]) -> String
+where
+    N: core::fmt::Display + core::fmt::Debug,
+    E: core::fmt::Display,
+{
+    assert!(
+        path.iter().all(|ix| ix.index() < graph.node_count()),
+        "every node of the path must be in the graph"
+    );
+
+    // The graph is embedded as a JS object, in which no string may close the script
+    let js_string = |text: String| {
+        let mut js = String::from("\"");
+        for c in text.chars() {
+            match c {
+                '"' => js.push_str("\\\""),
+                '\\' => js.push_str("\\\\"),
+                '\n' => js.push_str("\\n"),
+                '<' => js.push_str("\\u003c"),
+                c if c.is_control() => write!(js, "\\u{:04x}", c as u32).unwrap(),
+                c => js.push(c),
+            }
+        }
+        js.push('"');
+        js
+    };
+
+    let nodes = graph
+        .node_weights()
+        .map(|n| {
+            format!(
+                "{{\"display\":{},\"debug\":{}}}",
+                js_string(n.to_string()),
+                js_string(format!("{n:#?}"))
+            )
+        })
+        .collect::<Vec<_>>();
+    let edges = graph
+        .edge_references()
+        .map(|e| {
+            format!(
+                "[{},{},{}]",
+                e.source().index(),
+                e.target().index(),
+                js_string(e.weight().to_string())
+            )
+        })
+        .collect::<Vec<_>>();
+    let path = path
+        .iter()
+        .map(|ix| ix.index().to_string())
+        .collect::<Vec<_>>();
+    let data = format!(
+        "{{\"nodes\":[{}],\"edges\":[{}],\"path\":[{}]}}",
+        nodes.join(","),
+        edges.join(","),
+        path.join(",")
+    );
+
+    include_str!("diagram/explorer.html").replace("/*GRAPH*/", &data)
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
@@ -59,6 +324,7 @@ mod tests {
     #[derive(
         Copy, Clone, Debug, PartialEq, Eq, FromPrimitive, ToPrimitive, Hash, derive_more::Display,
     )]
+    #[cfg_attr(feature = "recording", derive(serde::Serialize, serde::Deserialize))]
     enum Cycle {
         A,
         B,
@@ -78,6 +344,7 @@ mod tests {
         ToPrimitive,
         derive_more::Display,
     )]
+    #[cfg_attr(feature = "recording", derive(serde::Serialize, serde::Deserialize))]
     enum Turn {
         One = 1,
         Two = 2,
@@ -165,4 +432,84 @@ mod tests {
         assert_eq!(nodes_exhaustive, nodes_traversal);
         assert_eq!(edges_exhaustive, edges_traversal);
     }
+
+    #[test]
+    #[cfg(feature = "recording")]
+    fn test_json_round_trip() {
+        let graph = CycleMachine.traverse([Cycle::D]).diagram().unwrap();
+        let json = to_json(&graph).unwrap();
+        let loaded: DiGraph<Cycle, Turn> = from_json(&json).unwrap();
+
+        assert!(graph.node_weights().eq(loaded.node_weights()));
+        let edges = |g: &DiGraph<Cycle, Turn>| {
+            g.edge_references()
+                .map(|e| (e.source(), e.target(), *e.weight()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(edges(&graph), edges(&loaded));
+        assert_eq!(to_json(&loaded).unwrap(), json);
+
+        let dangling = r#"{
+            "nodes": ["A"],
+            "edges": [{ "source": 0, "target": 1, "action": "One" }]
+        }"#;
+        assert!(from_json::<Cycle, Turn>(dangling).is_err());
+    }
+
+    #[test]
+    fn test_graphml_and_mermaid() {
+        let mut graph = DiGraph::new();
+        let a = graph.add_node("x < y");
+        let b = graph.add_node("a: 1\nb: 2");
+        graph.add_edge(a, b, "go; #1");
+
+        let graphml = to_graphml(&graph);
+        assert!(graphml.contains(r#"<node id="n0"><data key="state">x &lt; y</data></node>"#));
+        assert!(graphml.contains(r#"<edge id="e0" source="n0" target="n1">"#));
+        assert!(graphml.trim_end().ends_with("</graphml>"));
+
+        let mermaid = to_mermaid(&graph);
+        assert_eq!(
+            mermaid.lines().collect::<Vec<_>>(),
+            [
+                "stateDiagram-v2",
+                "    s0 : x < y",
+                "    s1 : a#58; 1<br/>b#58; 2",
+                "    s0 --> s1 : go#59; #35;1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_html() {
+        let graph = CycleMachine.traverse([Cycle::D]).diagram().unwrap();
+        let node = |state| graph.node_indices().find(|ix| graph[*ix] == state).unwrap();
+        // The path comes back to where it started, so it visits one state twice
+        let path = [Cycle::D, Cycle::A, Cycle::C, Cycle::D].map(node);
+        let html = to_html(&graph, &path);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(!html.contains("/*GRAPH*/"));
+        assert!(!html.contains("src=\"http"));
+
+        #[cfg(feature = "recording")]
+        {
+            let (_, data) = html.split_once("const GRAPH = ").unwrap();
+            let (data, _) = data.split_once(";\n").unwrap();
+            let data: serde_json::Value = serde_json::from_str(data).unwrap();
+            assert_eq!(data["nodes"].as_array().unwrap().len(), 4);
+            assert_eq!(data["edges"].as_array().unwrap().len(), 8);
+            assert_eq!(data["nodes"][0]["display"], "D");
+            assert_eq!(data["nodes"][0]["debug"], "D");
+            let indices = path.map(|ix| ix.index());
+            assert_eq!(data["path"], serde_json::json!(indices));
+            assert_eq!(indices[0], indices[3]);
+        }
+
+        // Nothing in a state can end the script early
+        let mut graph = DiGraph::<_, Turn>::new();
+        graph.add_node("</script>\n\"quoted\"");
+        let html = to_html(&graph, &[]);
+        assert_eq!(html.matches("</script>").count(), 1);
+        assert!(html.contains(r#""\u003c/script>\n\"quoted\"""#));
+    }
 }
@@ -0,0 +1,254 @@
+//! Minimizing state graphs by merging bisimilar states.
+//!
+//! Two states are bisimilar when each can match every action the other can take,
+//! leading to states which are bisimilar in turn, so nothing which happens from one
+//! of them can tell it apart from the other. Merging bisimilar states gives the
+//! smallest graph with the same behavior, which is much easier to read than the graph
+//! of a traversal (see [`Traversal::diagram`](crate::traversal::Traversal::diagram)),
+//! and can be compared with the minimized graph of another model.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    hash::Hash,
+};
+
+use itertools::Itertools;
+use petgraph::{
+    graph::{DiGraph, NodeIndex},
+    visit::EdgeRef,
+};
+
+/// The states merged into one node of a minimized graph, in the order of their nodes
+/// in the original graph.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, derive_more::Deref)]
+pub struct Merged<S>(pub Vec<S>);
+
+impl<S: Display> Display for Merged<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.iter().join("\n"))
+    }
+}
+
+/// Merge the states of a graph which are strongly bisimilar: those which can take
+/// the same actions as each other, to states which are strongly bisimilar in turn.
+///
+/// Each node of the minimized graph is labelled with the states it merges, and there is
+/// an edge for each action between them, whichever of the merged states it was taken from.
+pub fn minimize<S, A>(graph: &DiGraph<S, A>) -> DiGraph<Merged<S>, A>
+where
+    S: Clone,
+    A: Clone + Eq + Hash,
+{
+    minimize_by(graph, |_| false, |_| ())
+}
+
+/// Merge the states of a graph which are weakly bisimilar, treating the actions for which
+/// `is_internal` is true as internal steps, which can't be observed. Weakly bisimilar
+/// states can take the same observable actions as each other, each preceded and followed
+/// by any number of internal steps, to states which are weakly bisimilar in turn,
+/// and can reach weakly bisimilar states by internal steps alone.
+///
+/// Internal steps between states which are merged are left out of the minimized graph.
+pub fn minimize_weak<S, A>(
+    graph: &DiGraph<S, A>,
+    is_internal: impl Fn(&A) -> bool,
+) -> DiGraph<Merged<S>, A>
+where
+    S: Clone,
+    A: Clone + Eq + Hash,
+{
+    minimize_by(graph, is_internal, |_| ())
+}
+
+/// Merge the states of a graph which are weakly bisimilar (see [`minimize_weak`]),
+/// and which also have the same key, so that states which differ in some way that
+/// matters, like which propositions hold in them, are never merged.
+/// With no internal actions, this is strong bisimilarity (see [`minimize`]).
+pub fn minimize_by<S, A, K>(
+    graph: &DiGraph<S, A>,
+    is_internal: impl Fn(&A) -> bool,
+    key: impl Fn(&S) -> K,
+) -> DiGraph<Merged<S>, A>
+where
+    S: Clone,
+    A: Clone + Eq + Hash,
+    K: Eq + Hash,
+{
+    let blocks = partition(graph, &is_internal, key);
+
+    let mut minimized = DiGraph::new();
+    let mut nodes: HashMap<usize, NodeIndex> = HashMap::new();
+    for ix in graph.node_indices() {
+        let node = *nodes
+            .entry(blocks[ix.index()])
+            .or_insert_with(|| minimized.add_node(Merged(vec![])));
+        minimized[node].0.push(graph[ix].clone());
+    }
+
+    let mut edges = HashSet::new();
+    for edge in graph.edge_references() {
+        let source = nodes[&blocks[edge.source().index()]];
+        let target = nodes[&blocks[edge.target().index()]];
+        if source == target && is_internal(edge.weight()) {
+            continue;
+        }
+        if edges.insert((source, edge.weight(), target)) {
+            minimized.add_edge(source, target, edge.weight().clone());
+        }
+    }
+    minimized
+}
+
+/// The block of bisimilar states each node belongs to, by node index,
+/// found by splitting blocks until no block contains states with different signatures
+fn partition<S, A, K>(
+    graph: &DiGraph<S, A>,
+    is_internal: impl Fn(&A) -> bool,
+    key: impl Fn(&S) -> K,
+) -> Vec<usize>
+where
+    A: Eq + Hash,
+    K: Eq + Hash,
+{
+    // Number each observable action, leaving 0 for internal steps
+    let mut labels: HashMap<&A, usize> = HashMap::new();
+    let mut label = |a| {
+        if is_internal(a) {
+            0
+        } else {
+            let next = labels.len() + 1;
+            *labels.entry(a).or_insert(next)
+        }
+    };
+    let steps = graph
+        .node_indices()
+        .map(|ix| {
+            graph
+                .edges(ix)
+                .map(|e| (label(e.weight()), e.target().index()))
+                .collect_vec()
+        })
+        .collect_vec();
+    let transitions = weak_transitions(&steps);
+
+    let mut keys = HashMap::new();
+    let mut blocks = graph
+        .node_indices()
+        .map(|ix| {
+            let next = keys.len();
+            *keys.entry(key(&graph[ix])).or_insert(next)
+        })
+        .collect_vec();
+    let mut num_blocks = keys.len();
+    loop {
+        let mut signatures = HashMap::new();
+        let next = transitions
+            .iter()
+            .enumerate()
+            .map(|(i, transitions)| {
+                let signature: BTreeSet<(usize, usize)> = transitions
+                    .iter()
+                    .map(|&(label, target)| (label, blocks[target]))
+                    .collect();
+                let next = signatures.len();
+                *signatures.entry((blocks[i], signature)).or_insert(next)
+            })
+            .collect_vec();
+        // Blocks are only ever split, so they are stable once none are
+        if signatures.len() == num_blocks {
+            return blocks;
+        }
+        num_blocks = signatures.len();
+        blocks = next;
+    }
+}
+
+/// The weak transitions of each node, as labelled steps: each observable step preceded
+/// and followed by any number of internal steps (labelled 0), and any number of internal
+/// steps alone, including none
+fn weak_transitions(steps: &[Vec<(usize, usize)>]) -> Vec<BTreeSet<(usize, usize)>> {
+    // The nodes reachable from each node by internal steps alone
+    let closures = (0..steps.len())
+        .map(|start| {
+            let mut reached = BTreeSet::from([start]);
+            let mut stack = vec![start];
+            while let Some(node) = stack.pop() {
+                for &(label, target) in &steps[node] {
+                    if label == 0 && reached.insert(target) {
+                        stack.push(target);
+                    }
+                }
+            }
+            reached
+        })
+        .collect_vec();
+
+    closures
+        .iter()
+        .map(|closure| {
+            let mut transitions: BTreeSet<(usize, usize)> =
+                closure.iter().map(|&node| (0, node)).collect();
+            for &node in closure {
+                for &(label, target) in &steps[node] {
+                    if label != 0 {
+                        transitions.extend(closures[target].iter().map(|&after| (label, after)));
+                    }
+                }
+            }
+            transitions
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagram::to_dot;
+
+    #[test]
+    fn test_bisimulation() {
+        // Every state of the cycle can turn one or two steps, to another such state
+        let mut graph = DiGraph::new();
+        let cycle = ["a", "b", "c", "d"].map(|s| graph.add_node(s));
+        for i in 0..4 {
+            graph.add_edge(cycle[i], cycle[(i + 1) % 4], "one");
+            graph.add_edge(cycle[i], cycle[(i + 2) % 4], "two");
+        }
+        let minimized = minimize(&graph);
+        assert_eq!(minimized.node_count(), 1);
+        assert_eq!(minimized.edge_count(), 2);
+        assert_eq!(minimized.node_weights().next().unwrap().len(), 4);
+
+        // Unless one of them is marked
+        let minimized = minimize_by(&graph, |_| false, |s| *s == "a");
+        assert_eq!(minimized.node_count(), 4);
+        assert_eq!(minimized.edge_count(), 8);
+
+        // Both branches end the same way, so only the first step can tell them apart
+        let mut graph = DiGraph::new();
+        let [a, b, c, d, e] = ["a", "b", "c", "d", "e"].map(|s| graph.add_node(s));
+        graph.add_edge(a, b, "x");
+        graph.add_edge(a, c, "y");
+        graph.add_edge(b, d, "z");
+        graph.add_edge(c, e, "z");
+        let minimized = minimize(&graph);
+        let nodes: HashSet<_> = minimized.node_weights().map(|m| m.0.clone()).collect();
+        assert_eq!(nodes, [vec!["a"], vec!["b", "c"], vec!["d", "e"]].into());
+        assert_eq!(minimized.edge_count(), 3);
+
+        // With internal steps, a state which can only step internally to another
+        // is the same as that state
+        let mut graph = DiGraph::new();
+        let [a, b, c, d, e] = ["a", "b", "c", "d", "e"].map(|s| graph.add_node(s));
+        graph.add_edge(a, b, "tau");
+        graph.add_edge(b, c, "x");
+        graph.add_edge(d, e, "x");
+        assert_eq!(minimize(&graph).node_count(), 3);
+        let minimized = minimize_weak(&graph, |a| *a == "tau");
+        let nodes: HashSet<_> = minimized.node_weights().map(|m| m.0.clone()).collect();
+        assert_eq!(nodes, [vec!["a", "b", "d"], vec!["c", "e"]].into());
+        assert_eq!(minimized.edge_weights().collect::<Vec<_>>(), [&"x"]);
+        assert!(to_dot(&minimized, &[]).contains(r#"label = "a\lb\ld""#));
+    }
+}
@@ -127,6 +127,7 @@ where
 
 /// Generate a state diagram of this state machine by exhaustively taking all possible actions
 /// at each visited state.
+#[allow(clippy::type_complexity)]
 pub fn state_diagram_mapped<M, N, E>(
     machine: M,
     initial: M::State,
@@ -178,15 +179,13 @@ where
         };
 
         // Add an edge from the previous node to this node.
-        if let Some((prev_action, prev_ix)) = origin {
-            if let Some(edge) = map_edge(prev_action) {
-                if !(config.ignore_loopbacks && prev_ix == ix)
-                    && visited_edges.insert((prev_ix, ix, edge.clone()))
-                {
-                    tracing::debug!("new edge : {prev_ix:?}->{ix:?} {edge:?}");
-                    graph.add_edge(prev_ix, ix, edge);
-                }
-            }
+        if let Some((prev_action, prev_ix)) = origin
+            && let Some(edge) = map_edge(prev_action)
+            && !(config.ignore_loopbacks && prev_ix == ix)
+            && visited_edges.insert((prev_ix, ix, edge.clone()))
+        {
+            tracing::debug!("new edge : {prev_ix:?}->{ix:?} {edge:?}");
+            graph.add_edge(prev_ix, ix, edge);
         }
 
         // Don't explore the same node twice.
@@ -205,7 +204,13 @@ where
         }
 
         // Queue up visits to all nodes reachable from this node..
-        for edge in M::Action::iter_exhaustive(config.max_actions) {
+        let actions = match machine.enabled_actions(&state) {
+            Some(actions) => actions
+                .take(config.max_actions.unwrap_or(usize::MAX))
+                .collect(),
+            None => M::Action::iter_exhaustive(config.max_actions).collect::<Vec<_>>(),
+        };
+        for edge in actions {
             total_steps += 1;
             match machine.transition(state.clone(), edge.clone()).map(first) {
                 Ok(node) => {
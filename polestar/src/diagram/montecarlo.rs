@@ -1,6 +1,9 @@
 //! Experimental feature for producing "Monte-Carlo state diagrams" from
 //! random walks through state machines.
 //!
+//! To check a machine with random walks, see
+//! [`Traversal::simulate`](crate::traversal::Traversal::simulate).
+//!
 // TODO: more documentation and context
 
 use std::{
@@ -153,6 +156,7 @@ where
     M::Action: Arbitrary + Clone + Eq + Hash + 'static,
     S: MonteCarloDiagramState<M>,
 {
+    use proptest::prelude::RngExt;
     use proptest::strategy::ValueTree;
     use proptest::test_runner::TestRunner;
     let mut runner = TestRunner::default();
@@ -163,11 +167,21 @@ where
     let mut terminated = false;
     while num_steps < steps {
         num_steps += 1;
-        let action: M::Action = diagram_state
-            .strategy()
-            .new_tree(&mut runner)
-            .unwrap()
-            .current();
+        // Take one of the actions the machine lists, if it lists them, and stop if there are none
+        let action: M::Action = match machine.enabled_actions(&model_state) {
+            Some(actions) => {
+                let mut actions: Vec<_> = actions.collect();
+                if actions.is_empty() {
+                    break;
+                }
+                actions.swap_remove(runner.rng().random_range(0..actions.len()))
+            }
+            None => diagram_state
+                .strategy()
+                .new_tree(&mut runner)
+                .unwrap()
+                .current(),
+        };
 
         diagram_state.on_action(&action);
 
@@ -344,6 +344,53 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_fetch_timed_refines_timeless() {
+        use crate::example_models::fetch_timeless;
+
+        type Agent = UpTo<2>;
+        type Val = UpTo<1>;
+
+        let model = super::Model::<Agent, Val, Time>::new(
+            UpTo::new(TIMEOUT).into(),
+            UpTo::new(TIMEOUT_GRACE).into(),
+            Agent::all_values().to_vec(),
+        );
+        let timeless = fetch_timeless::Model::<Agent, Val>::new(Agent::all_values().to_vec());
+        let initial = model.initial();
+
+        // Forget the clocks, and with them every tick
+        let abstract_state = |state: &State<Agent, Val, Time>| fetch_timeless::State {
+            nodes: state
+                .nodes
+                .iter()
+                .map(|(n, s)| {
+                    let node = fetch_timeless::NodeState {
+                        values: s.values.clone(),
+                        requests: s.requests.iter().map(|r| r.val).collect(),
+                    };
+                    (*n, node)
+                })
+                .collect(),
+        };
+        let abstract_actions = |(node, action): &Action<Agent, Val, Time>| {
+            use fetch_timeless::NodeAction as A;
+            match *action {
+                NodeAction::Tick(_) => vec![],
+                NodeAction::Author(v) => vec![(*node, A::Author(v))],
+                NodeAction::Request(v, from) => vec![(*node, A::Request(v, from))],
+                NodeAction::Timeout(v) => vec![(*node, A::Timeout(v))],
+                NodeAction::Receive(v, found) => vec![(*node, A::Receive(v, found))],
+            }
+        };
+
+        let violation = model
+            .traverse([initial])
+            .check_refinement(&timeless, abstract_state, abstract_actions)
+            .unwrap();
+        assert!(violation.is_none(), "{violation:?}");
+    }
+
     #[test]
     #[ignore = "slow"]
     fn test_fetch_timed() {
@@ -0,0 +1,128 @@
+//! The fetch model without clocks, of which [`fetch_timed`](super::fetch_timed) is a refinement
+
+use std::marker::PhantomData;
+
+use crate::prelude::*;
+use anyhow::{anyhow, bail};
+use im::{OrdMap, OrdSet, Vector};
+
+/// Action for fetch_timeless
+pub type Action<Agent, Val> = (Agent, NodeAction<Agent, Val>);
+
+/// Action taken by a single node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, exhaustive::Exhaustive, derive_more::Display)]
+pub enum NodeAction<Agent, Val> {
+    /// Create a new value to be fetched by others
+    #[display("Auth(v{_0})")]
+    Author(Val),
+
+    /// Request a value be sent
+    #[display("Req(v{_0} ⇐ n{_1})")]
+    Request(Val, Agent),
+
+    /// Stop waiting on a request, at any time
+    #[display("Timeout(v{_0})")]
+    Timeout(Val),
+
+    /// Receive and store a value
+    #[display("Recv(v{_0}, {_1})")]
+    Receive(Val, bool),
+}
+
+/// State for fetch_timeless
+#[derive(Debug, Clone, PartialEq, Eq, Hash, derive_more::Constructor)]
+pub struct State<Agent: Clone + Ord, Val: Clone + Ord> {
+    /// The nodes in the system
+    pub nodes: OrdMap<Agent, NodeState<Val>>,
+}
+
+/// State for a single node
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NodeState<Val: Clone + Ord> {
+    /// The values stored by this node
+    pub values: OrdSet<Val>,
+
+    /// The values this node has outstanding requests for
+    pub requests: Vector<Val>,
+}
+
+impl<Val: Id> Default for NodeState<Val> {
+    fn default() -> Self {
+        Self {
+            values: OrdSet::new(),
+            requests: Vector::new(),
+        }
+    }
+}
+
+/// Model for fetch_timeless
+pub struct Model<Agent: Id, Val: Id> {
+    /// The nodes in the system
+    nodes: Vec<Agent>,
+
+    phantom: PhantomData<Val>,
+}
+
+impl<Agent: Id, Val: Id> Model<Agent, Val> {
+    /// Constructor
+    pub fn new(nodes: Vec<Agent>) -> Self {
+        Self {
+            nodes,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Initial state
+    pub fn initial(&self) -> State<Agent, Val> {
+        State {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|n| (*n, NodeState::default()))
+                .collect(),
+        }
+    }
+}
+
+impl<Agent: Id, Val: Id> Machine for Model<Agent, Val> {
+    type State = State<Agent, Val>;
+    type Action = Action<Agent, Val>;
+    type Error = anyhow::Error;
+    type Fx = ();
+
+    fn transition(
+        &self,
+        mut state: Self::State,
+        (node, action): Self::Action,
+    ) -> TransitionResult<Self> {
+        match action {
+            NodeAction::Author(v) => {
+                state.nodes[&node].values.insert(v);
+            }
+            NodeAction::Request(v, _from) => {
+                if state.nodes[&node].requests.contains(&v) {
+                    bail!("request already exists")
+                }
+                if state.nodes[&node].values.contains(&v) {
+                    bail!("value already stored, don't request it again")
+                }
+                state.nodes[&node].requests.push_back(v);
+            }
+            NodeAction::Timeout(v) => {
+                let ix = state.nodes[&node]
+                    .requests
+                    .index_of(&v)
+                    .ok_or(anyhow!("no requests to timeout"))?;
+                state.nodes[&node].requests.remove(ix);
+            }
+            NodeAction::Receive(v, found) => {
+                if found {
+                    state.nodes[&node].values.insert(v);
+                }
+                state.nodes[&node].requests.retain(|r| *r != v);
+            }
+        }
+
+        Ok((state, ()))
+    }
+}
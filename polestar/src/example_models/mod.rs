@@ -1,3 +1,8 @@
-//! Example models, used in this crate's examples.
+//! Example models, used in this crate's examples and tests.
+//!
+//! - [`fetch_timed`]: nodes fetching values from each other, with clocks for timeouts
+//! - [`fetch_timeless`]: the same model without clocks, which [`fetch_timed`] is checked
+//!   to refine (see [`Traversal::check_refinement`](crate::traversal::Traversal::check_refinement))
 
 pub mod fetch_timed;
+pub mod fetch_timeless;
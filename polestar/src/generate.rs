@@ -13,14 +13,14 @@ pub trait Generator {
     }
 
     /// Generate a single value with a custom strategy
-    fn generate_with<T: Arbitrary>(
+    fn generate_with<T>(
         &mut self,
         strategy: impl Strategy<Value = T>,
     ) -> Result<T, prop::test_runner::Reason>;
 }
 
 impl Generator for prop::test_runner::TestRunner {
-    fn generate_with<T: Arbitrary>(
+    fn generate_with<T>(
         &mut self,
         strategy: impl Strategy<Value = T>,
     ) -> Result<T, prop::test_runner::Reason> {
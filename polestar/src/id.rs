@@ -4,6 +4,8 @@ use std::{collections::HashMap, hash::Hash};
 
 mod bag;
 pub use bag::*;
+mod relabel;
+pub use relabel::*;
 mod upto;
 pub use upto::*;
 
@@ -252,6 +254,60 @@ mod tests {
         assert_eq!(b - (3 * 100 + 1), UpTo(1));
     }
 
+    #[test]
+    fn test_symmetry() {
+        #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Relabel)]
+        enum Msg<N: Id> {
+            Ping { from: N, to: N },
+            Seen(std::collections::BTreeSet<N>, u8),
+            Nothing,
+        }
+
+        type N = UpTo<3>;
+        let symmetry = Symmetry::<N>::full();
+        assert_eq!(symmetry.len(), 6);
+
+        let ping = Msg::Ping {
+            from: N::new(2),
+            to: N::new(1),
+        };
+        let relabelings: Vec<_> = symmetry.relabelings(&ping).collect();
+        assert_eq!(relabelings[0], ping);
+        assert_eq!(
+            relabelings
+                .iter()
+                .collect::<std::collections::HashSet<_>>()
+                .len(),
+            6
+        );
+        for r in &relabelings {
+            assert_eq!(
+                symmetry.canonical(r),
+                Msg::Ping {
+                    from: N::new(0),
+                    to: N::new(1)
+                }
+            );
+        }
+
+        // Only the Id type being permuted is relabeled
+        let seen = Msg::Seen([N::new(1), N::new(2)].into(), 2);
+        assert_eq!(
+            symmetry.canonical(&seen),
+            Msg::Seen([N::new(0), N::new(1)].into(), 2)
+        );
+        assert_eq!(Symmetry::<UpTo<2>>::full().canonical(&seen), seen);
+        assert_eq!(symmetry.canonical(&Msg::<N>::Nothing), Msg::Nothing);
+
+        // Other values can be relabeled by the permutation which canonicalizes one
+        let (canonical, permutation) = symmetry.canonicalize(&ping);
+        assert_eq!(symmetry.relabel(&ping, permutation), canonical);
+        assert_eq!(
+            symmetry.relabel(&Msg::Seen([N::new(2)].into(), 0), permutation),
+            Msg::Seen([N::new(0)].into(), 0)
+        );
+    }
+
     #[test]
     fn test_id_map() {
         let mut m = IdMap::<_, UpTo<3>>::default();
@@ -0,0 +1,337 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use itertools::Itertools;
+
+use super::*;
+
+pub use polestar_macros::Relabel;
+
+/// A value which can be rewritten under a permutation of the values of the [`Id`] type `I`,
+/// by replacing every `I` it contains with its image under the permutation.
+///
+/// This is how symmetry is expressed: if a model treats all values of `I` alike,
+/// states which are relabelings of each other are equivalent, so only one of them
+/// needs to be explored (see [`Traversal::symmetry`](crate::traversal::Traversal::symmetry)).
+///
+/// Every `Id` type implements `Relabel<I>` for every `I`, being permuted only if it is `I`,
+/// and other types with no `Id` inside are left unchanged.
+/// For your own types, derive it:
+///
+/// ```
+/// use polestar::prelude::*;
+/// use std::collections::BTreeMap;
+///
+/// #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Relabel)]
+/// struct State<N: Id> {
+///     leader: Option<N>,
+///     votes: BTreeMap<N, N>,
+/// }
+///
+/// type N = UpTo<3>;
+/// let state = State {
+///     leader: Some(N::new(2)),
+///     votes: [(N::new(0), N::new(2)), (N::new(1), N::new(2))].into(),
+/// };
+/// let swapped = Relabel::<N>::relabel(&state, &|n: N| N::new(2 - *n));
+/// assert_eq!(swapped.leader, Some(N::new(0)));
+/// assert_eq!(swapped.votes, [(N::new(2), N::new(0)), (N::new(1), N::new(0))].into());
+/// ```
+///
+/// Note that a value indexed by position, like an array with one item per `Id` value,
+/// is not relabeled as a whole: use a map keyed by the `Id` instead.
+pub trait Relabel<I: Id>: Sized {
+    /// Rewrite every `I` in this value by the permutation `f`.
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self;
+}
+
+/// All permutations of the values of an [`Id`] type, which must have a small number of values.
+///
+/// With N values there are N! permutations, so this is only practical for small N.
+#[derive(Clone, Debug)]
+pub struct Symmetry<I: Id> {
+    values: Vec<I>,
+    permutations: Vec<Vec<usize>>,
+}
+
+impl<I: Id> Symmetry<I> {
+    /// The full symmetry group of `I`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `I` does not have a small number of values (see [`IdChoices`]).
+    pub fn full() -> Self {
+        let IdChoices::Small(n) = I::choices() else {
+            panic!(
+                "symmetry requires an Id type with a small number of values, which {} is not",
+                std::any::type_name::<I>()
+            );
+        };
+        let values = (0..n)
+            .map(|i| {
+                I::try_from(i).unwrap_or_else(|_| {
+                    panic!("{} has {n} values but not {i}", std::any::type_name::<I>())
+                })
+            })
+            .collect();
+        let permutations = (0..n).permutations(n).collect();
+        Self {
+            values,
+            permutations,
+        }
+    }
+
+    /// The number of permutations.
+    pub fn len(&self) -> usize {
+        self.permutations.len()
+    }
+
+    /// Whether there are no permutations, which is never the case.
+    pub fn is_empty(&self) -> bool {
+        self.permutations.is_empty()
+    }
+
+    /// Every relabeling of the value, one per permutation, beginning with the value itself.
+    pub fn relabelings<'a, T: Relabel<I>>(&'a self, value: &'a T) -> impl Iterator<Item = T> + 'a {
+        (0..self.len()).map(move |permutation| self.relabel(value, permutation))
+    }
+
+    /// Relabel the value by the permutation with the given index, which is less than
+    /// [`Symmetry::len`], and 0 for the identity permutation.
+    pub fn relabel<T: Relabel<I>>(&self, value: &T, permutation: usize) -> T {
+        let permutation = &self.permutations[permutation];
+        value.relabel(&|id| {
+            let i = self
+                .values
+                .iter()
+                .position(|v| *v == id)
+                .expect("every Id value is one of its choices");
+            self.values[permutation[i]]
+        })
+    }
+
+    /// The lexicographically smallest relabeling of the value, which is the same
+    /// for all values which are relabelings of each other.
+    pub fn canonical<T: Relabel<I> + Ord>(&self, value: &T) -> T {
+        self.canonicalize(value).0
+    }
+
+    /// The canonical relabeling of the value (see [`Symmetry::canonical`]), along with
+    /// the index of the permutation which gives it, so that related values, like an
+    /// action taken in a state, can be relabeled alike (see [`Symmetry::relabel`]).
+    pub fn canonicalize<T: Relabel<I> + Ord>(&self, value: &T) -> (T, usize) {
+        (0..self.len())
+            .map(|permutation| (self.relabel(value, permutation), permutation))
+            .min_by(|(a, _), (b, _)| a.cmp(b))
+            .expect("there is always the identity permutation")
+    }
+}
+
+/// Permute an `Id` value if it is of the type being permuted
+fn relabel_id<I: Id, T: Id>(id: &T, f: &impl Fn(I) -> I) -> T {
+    match (id as &dyn Any).downcast_ref::<I>() {
+        Some(id) => *(&f(*id) as &dyn Any).downcast_ref::<T>().unwrap(),
+        None => *id,
+    }
+}
+
+impl<I: Id, const N: usize, const WRAP: bool> Relabel<I> for UpTo<N, WRAP> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        relabel_id(self, f)
+    }
+}
+
+#[cfg(feature = "nonessential")]
+impl<I: Id, const UID: u64> Relabel<I> for UpToLazy<UID> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        relabel_id(self, f)
+    }
+}
+
+macro_rules! relabel_ids {
+    ($($t:ty),*) => {
+        $(
+            impl<I: Id> Relabel<I> for $t {
+                fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+                    relabel_id(self, f)
+                }
+            }
+        )*
+    };
+}
+
+relabel_ids!(u8, u16, u32, u64, usize, IdUnit);
+
+macro_rules! relabel_unchanged {
+    ($($t:ty),*) => {
+        $(
+            impl<I: Id> Relabel<I> for $t {
+                fn relabel(&self, _: &impl Fn(I) -> I) -> Self {
+                    self.clone()
+                }
+            }
+        )*
+    };
+}
+
+relabel_unchanged!(
+    (),
+    bool,
+    char,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u128,
+    String,
+    &'static str
+);
+
+impl<I: Id, T> Relabel<I> for PhantomData<T> {
+    fn relabel(&self, _: &impl Fn(I) -> I) -> Self {
+        PhantomData
+    }
+}
+
+impl<I: Id, T: Relabel<I>> Relabel<I> for Option<T> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        self.as_ref().map(|t| t.relabel(f))
+    }
+}
+
+impl<I: Id, T: Relabel<I>, E: Relabel<I>> Relabel<I> for Result<T, E> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        match self {
+            Ok(t) => Ok(t.relabel(f)),
+            Err(e) => Err(e.relabel(f)),
+        }
+    }
+}
+
+impl<I: Id, T: Relabel<I>> Relabel<I> for Box<T> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        Box::new((**self).relabel(f))
+    }
+}
+
+impl<I: Id, T: Relabel<I>> Relabel<I> for Arc<T> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        Arc::new((**self).relabel(f))
+    }
+}
+
+impl<I: Id, T: Relabel<I>, const N: usize> Relabel<I> for [T; N] {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        std::array::from_fn(|i| self[i].relabel(f))
+    }
+}
+
+macro_rules! relabel_tuples {
+    ($(($($t:ident $i:tt),+))*) => {
+        $(
+            impl<I: Id, $($t: Relabel<I>),+> Relabel<I> for ($($t,)+) {
+                fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+                    ($(self.$i.relabel(f),)+)
+                }
+            }
+        )*
+    };
+}
+
+relabel_tuples! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+}
+
+impl<I: Id, T: Relabel<I>> Relabel<I> for Vec<T> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        self.iter().map(|t| t.relabel(f)).collect()
+    }
+}
+
+impl<I: Id, T: Relabel<I>> Relabel<I> for VecDeque<T> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        self.iter().map(|t| t.relabel(f)).collect()
+    }
+}
+
+impl<I: Id, T: Relabel<I> + Ord> Relabel<I> for BTreeSet<T> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        self.iter().map(|t| t.relabel(f)).collect()
+    }
+}
+
+impl<I: Id, K: Relabel<I> + Ord, V: Relabel<I>> Relabel<I> for BTreeMap<K, V> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        self.iter()
+            .map(|(k, v)| (k.relabel(f), v.relabel(f)))
+            .collect()
+    }
+}
+
+impl<I: Id, T, H> Relabel<I> for HashSet<T, H>
+where
+    T: Relabel<I> + Eq + Hash,
+    H: BuildHasher + Default,
+{
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        self.iter().map(|t| t.relabel(f)).collect()
+    }
+}
+
+impl<I: Id, K, V, H> Relabel<I> for HashMap<K, V, H>
+where
+    K: Relabel<I> + Eq + Hash,
+    V: Relabel<I>,
+    H: BuildHasher + Default,
+{
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        self.iter()
+            .map(|(k, v)| (k.relabel(f), v.relabel(f)))
+            .collect()
+    }
+}
+
+impl<I: Id, T: Relabel<I> + Clone> Relabel<I> for im::Vector<T> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        self.iter().map(|t| t.relabel(f)).collect()
+    }
+}
+
+impl<I: Id, T: Relabel<I> + Ord + Clone> Relabel<I> for im::OrdSet<T> {
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        self.iter().map(|t| t.relabel(f)).collect()
+    }
+}
+
+impl<I: Id, K, V> Relabel<I> for im::OrdMap<K, V>
+where
+    K: Relabel<I> + Ord + Clone,
+    V: Relabel<I> + Clone,
+{
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        self.iter()
+            .map(|(k, v)| (k.relabel(f), v.relabel(f)))
+            .collect()
+    }
+}
+
+impl<I: Id, const N: usize, T> Relabel<I> for Bag<N, T>
+where
+    T: Relabel<I> + Ord + TryFrom<usize>,
+{
+    fn relabel(&self, f: &impl Fn(I) -> I) -> Self {
+        Bag::new(self.iter().map(|t| t.relabel(f)))
+    }
+}
@@ -11,6 +11,9 @@
 #![warn(missing_docs)]
 #![cfg_attr(nightly, feature(associated_type_defaults))]
 
+// Lets derived code refer to `::polestar` from within this crate too
+extern crate self as polestar;
+
 pub mod event_handler;
 pub mod ext;
 pub mod generate;
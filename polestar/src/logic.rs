@@ -10,9 +10,10 @@ use crate::Machine;
 #[cfg(feature = "ltl3ba")]
 mod ltl3ba_parser;
 
-#[cfg(feature = "ltl3ba")]
+mod ltl;
+pub use ltl::*;
+
 mod propositions;
-#[cfg(feature = "ltl3ba")]
 pub use propositions::*;
 
 /// A propositional logic statement
@@ -0,0 +1,461 @@
+//! Linear temporal logic formulae, and a parser for the textual syntax
+//! accepted by `ltl2ba`/`ltl3ba`.
+//!
+//! The syntax is:
+//! - atomic propositions: identifiers like `p`, `is_ready`, `hungry_1`
+//! - constants: `true`, `false`
+//! - boolean operators: `!`, `&&`, `||`, `->`, `<->`
+//! - unary temporal operators: `X` (next), `F` or `<>` (finally), `G` or `[]` (globally)
+//! - binary temporal operators: `U` (until), `R` or `V` (release), `W` (weak until)
+//!
+//! Unary operators bind tightest, followed by the binary temporal operators,
+//! then `&&`, `||`, `->` and finally `<->`. Binary temporal operators and `->`
+//! are right-associative.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, bail};
+
+use super::LogicStatement;
+
+/// A linear temporal logic formula over atomic propositions named by strings.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Ltl {
+    /// True
+    True,
+    /// False
+    False,
+
+    /// An atomic proposition
+    Prop(String),
+
+    /// Logical NOT
+    Not(BoxLtl),
+    /// Logical AND
+    And(BoxLtl, BoxLtl),
+    /// Logical OR
+    Or(BoxLtl, BoxLtl),
+    /// Logical implication
+    Implies(BoxLtl, BoxLtl),
+    /// Logical equivalence
+    Iff(BoxLtl, BoxLtl),
+
+    /// The formula holds at the next step
+    Next(BoxLtl),
+    /// The formula holds at some point in the future
+    Finally(BoxLtl),
+    /// The formula holds at every point in the future
+    Globally(BoxLtl),
+    /// The first formula holds at least until the second one does, which must eventually hold
+    Until(BoxLtl, BoxLtl),
+    /// The second formula holds up to and including the point where the first one holds,
+    /// if ever
+    Release(BoxLtl, BoxLtl),
+    /// Like [`Ltl::Until`], except the second formula need not ever hold
+    WeakUntil(BoxLtl, BoxLtl),
+}
+
+type BoxLtl = Box<Ltl>;
+
+#[allow(clippy::should_implement_trait)]
+impl Ltl {
+    /// Parse a formula from the `ltl2ba`/`ltl3ba` syntax.
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let ltl = parser.iff()?;
+        if let Some(token) = parser.peek() {
+            bail!("unexpected token '{token}' in LTL formula: {input}");
+        }
+        Ok(ltl)
+    }
+
+    /// Combine with NOT
+    pub fn not(self) -> Self {
+        Ltl::Not(Box::new(self))
+    }
+
+    /// Combine with AND
+    pub fn and(self, p2: Self) -> Self {
+        Ltl::And(Box::new(self), Box::new(p2))
+    }
+
+    /// Combine with OR
+    pub fn or(self, p2: Self) -> Self {
+        Ltl::Or(Box::new(self), Box::new(p2))
+    }
+
+    /// Combine with implication
+    pub fn implies(self, p2: Self) -> Self {
+        Ltl::Implies(Box::new(self), Box::new(p2))
+    }
+
+    /// Wrap with NEXT
+    pub fn next(self) -> Self {
+        Ltl::Next(Box::new(self))
+    }
+
+    /// Wrap with FINALLY
+    pub fn finally(self) -> Self {
+        Ltl::Finally(Box::new(self))
+    }
+
+    /// Wrap with GLOBALLY
+    pub fn globally(self) -> Self {
+        Ltl::Globally(Box::new(self))
+    }
+
+    /// Combine with UNTIL
+    pub fn until(self, p2: Self) -> Self {
+        Ltl::Until(Box::new(self), Box::new(p2))
+    }
+
+    /// Combine with RELEASE
+    pub fn release(self, p2: Self) -> Self {
+        Ltl::Release(Box::new(self), Box::new(p2))
+    }
+
+    /// Whether this formula contains no temporal operators.
+    pub fn is_propositional(&self) -> bool {
+        match self {
+            Ltl::True | Ltl::False | Ltl::Prop(_) => true,
+            Ltl::Not(p) => p.is_propositional(),
+            Ltl::And(p1, p2) | Ltl::Or(p1, p2) | Ltl::Implies(p1, p2) | Ltl::Iff(p1, p2) => {
+                p1.is_propositional() && p2.is_propositional()
+            }
+            Ltl::Next(_)
+            | Ltl::Finally(_)
+            | Ltl::Globally(_)
+            | Ltl::Until(_, _)
+            | Ltl::Release(_, _)
+            | Ltl::WeakUntil(_, _) => false,
+        }
+    }
+
+    /// Convert to negation normal form, where negations only appear directly
+    /// in front of atomic propositions, and the only operators used are
+    /// AND, OR, NEXT, UNTIL and RELEASE.
+    pub fn nnf(self) -> Self {
+        self.nnf_polarity(true)
+    }
+
+    fn nnf_polarity(self, positive: bool) -> Self {
+        let nnf = |p: BoxLtl, pos: bool| Box::new(p.nnf_polarity(pos));
+        match (self, positive) {
+            (Ltl::True, true) | (Ltl::False, false) => Ltl::True,
+            (Ltl::False, true) | (Ltl::True, false) => Ltl::False,
+            (Ltl::Prop(p), true) => Ltl::Prop(p),
+            (Ltl::Prop(p), false) => Ltl::Prop(p).not(),
+            (Ltl::Not(p), pos) => p.nnf_polarity(!pos),
+            (Ltl::And(p1, p2), true) | (Ltl::Or(p1, p2), false) => {
+                Ltl::And(nnf(p1, positive), nnf(p2, positive))
+            }
+            (Ltl::Or(p1, p2), true) | (Ltl::And(p1, p2), false) => {
+                Ltl::Or(nnf(p1, positive), nnf(p2, positive))
+            }
+            (Ltl::Implies(p1, p2), pos) => p1.not().or(*p2).nnf_polarity(pos),
+            (Ltl::Iff(p1, p2), pos) => {
+                let both = (*p1).clone().and((*p2).clone());
+                let neither = p1.not().and(p2.not());
+                both.or(neither).nnf_polarity(pos)
+            }
+            (Ltl::Next(p), pos) => Ltl::Next(nnf(p, pos)),
+            (Ltl::Finally(p), pos) => Ltl::True.until(*p).nnf_polarity(pos),
+            (Ltl::Globally(p), pos) => Ltl::False.release(*p).nnf_polarity(pos),
+            (Ltl::Until(p1, p2), true) | (Ltl::Release(p1, p2), false) => {
+                Ltl::Until(nnf(p1, positive), nnf(p2, positive))
+            }
+            (Ltl::Release(p1, p2), true) | (Ltl::Until(p1, p2), false) => {
+                Ltl::Release(nnf(p1, positive), nnf(p2, positive))
+            }
+            (Ltl::WeakUntil(p1, p2), pos) => {
+                let either = (*p1).or((*p2).clone());
+                (*p2).release(either).nnf_polarity(pos)
+            }
+        }
+    }
+
+    /// Convert a propositional formula into a [`LogicStatement`].
+    /// Returns None if the formula contains temporal operators.
+    pub fn to_logic_statement(&self) -> Option<LogicStatement> {
+        let both = |p1: &Ltl, p2: &Ltl| Some((p1.to_logic_statement()?, p2.to_logic_statement()?));
+        Some(match self {
+            Ltl::True => LogicStatement::True,
+            Ltl::False => LogicStatement::False,
+            Ltl::Prop(name) => LogicStatement::Prop(name.clone()),
+            Ltl::Not(p) => p.to_logic_statement()?.not(),
+            Ltl::And(p1, p2) => {
+                let (p1, p2) = both(p1, p2)?;
+                p1.and(p2)
+            }
+            Ltl::Or(p1, p2) => {
+                let (p1, p2) = both(p1, p2)?;
+                p1.or(p2)
+            }
+            Ltl::Implies(p1, p2) => {
+                let (p1, p2) = both(p1, p2)?;
+                p1.implies(p2)
+            }
+            Ltl::Iff(p1, p2) => {
+                let (p1, p2) = both(p1, p2)?;
+                p1.clone().implies(p2.clone()).and(p2.implies(p1))
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl From<&str> for Ltl {
+    fn from(s: &str) -> Self {
+        Ltl::Prop(s.to_string())
+    }
+}
+
+impl FromStr for Ltl {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ltl::parse(s)
+    }
+}
+
+/// Displays the formula in the same syntax accepted by [`Ltl::parse`],
+/// fully parenthesized.
+impl Display for Ltl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ltl::True => write!(f, "true"),
+            Ltl::False => write!(f, "false"),
+            Ltl::Prop(name) => write!(f, "{name}"),
+            Ltl::Not(p) => write!(f, "!{p}"),
+            Ltl::And(p1, p2) => write!(f, "({p1} && {p2})"),
+            Ltl::Or(p1, p2) => write!(f, "({p1} || {p2})"),
+            Ltl::Implies(p1, p2) => write!(f, "({p1} -> {p2})"),
+            Ltl::Iff(p1, p2) => write!(f, "({p1} <-> {p2})"),
+            Ltl::Next(p) => write!(f, "X {p}"),
+            Ltl::Finally(p) => write!(f, "F {p}"),
+            Ltl::Globally(p) => write!(f, "G {p}"),
+            Ltl::Until(p1, p2) => write!(f, "({p1} U {p2})"),
+            Ltl::Release(p1, p2) => write!(f, "({p1} R {p2})"),
+            Ltl::WeakUntil(p1, p2) => write!(f, "({p1} W {p2})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+enum Token {
+    #[display("{_0}")]
+    Ident(String),
+    #[display("(")]
+    LParen,
+    #[display(")")]
+    RParen,
+    #[display("!")]
+    Not,
+    #[display("&&")]
+    And,
+    #[display("||")]
+    Or,
+    #[display("->")]
+    Implies,
+    #[display("<->")]
+    Iff,
+    #[display("G")]
+    Globally,
+    #[display("F")]
+    Finally,
+}
+
+fn tokenize(input: &str) -> anyhow::Result<Vec<Token>> {
+    const SYMBOLS: &[(&str, Token)] = &[
+        ("<->", Token::Iff),
+        ("->", Token::Implies),
+        ("&&", Token::And),
+        ("||", Token::Or),
+        ("[]", Token::Globally),
+        ("<>", Token::Finally),
+        ("&", Token::And),
+        ("|", Token::Or),
+        ("!", Token::Not),
+        ("~", Token::Not),
+        ("(", Token::LParen),
+        (")", Token::RParen),
+    ];
+
+    let mut tokens = vec![];
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let ident_len = rest
+            .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+            .unwrap_or(rest.len());
+        if ident_len > 0 {
+            tokens.push(Token::Ident(rest[..ident_len].to_string()));
+            rest = &rest[ident_len..];
+        } else if let Some((symbol, token)) = SYMBOLS.iter().find(|(sym, _)| rest.starts_with(sym))
+        {
+            tokens.push(token.clone());
+            rest = &rest[symbol.len()..];
+        } else {
+            bail!("unexpected input '{rest}' in LTL formula: {input}");
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_ident(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Ident(name)) => Some(name.as_str()),
+            _ => None,
+        }
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn iff(&mut self) -> anyhow::Result<Ltl> {
+        let mut lhs = self.implies()?;
+        while self.eat(&Token::Iff) {
+            let rhs = self.implies()?;
+            lhs = Ltl::Iff(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn implies(&mut self) -> anyhow::Result<Ltl> {
+        let lhs = self.or()?;
+        if self.eat(&Token::Implies) {
+            let rhs = self.implies()?;
+            Ok(lhs.implies(rhs))
+        } else {
+            Ok(lhs)
+        }
+    }
+
+    fn or(&mut self) -> anyhow::Result<Ltl> {
+        let mut lhs = self.and()?;
+        while self.eat(&Token::Or) {
+            lhs = lhs.or(self.and()?);
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Ltl> {
+        let mut lhs = self.binary_temporal()?;
+        while self.eat(&Token::And) {
+            lhs = lhs.and(self.binary_temporal()?);
+        }
+        Ok(lhs)
+    }
+
+    fn binary_temporal(&mut self) -> anyhow::Result<Ltl> {
+        let lhs = self.unary()?;
+        let op: fn(BoxLtl, BoxLtl) -> Ltl = match self.peek_ident() {
+            Some("U") => Ltl::Until,
+            Some("R") | Some("V") => Ltl::Release,
+            Some("W") => Ltl::WeakUntil,
+            _ => return Ok(lhs),
+        };
+        self.advance();
+        let rhs = self.binary_temporal()?;
+        Ok(op(Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Ltl> {
+        let token = self
+            .advance()
+            .ok_or_else(|| anyhow!("unexpected end of LTL formula"))?;
+        Ok(match token {
+            Token::Not => self.unary()?.not(),
+            Token::Globally => self.unary()?.globally(),
+            Token::Finally => self.unary()?.finally(),
+            Token::LParen => {
+                let inner = self.iff()?;
+                if !self.eat(&Token::RParen) {
+                    bail!("missing closing parenthesis in LTL formula");
+                }
+                inner
+            }
+            Token::Ident(name) => match name.as_str() {
+                "true" | "1" => Ltl::True,
+                "false" | "0" => Ltl::False,
+                "X" => self.unary()?.next(),
+                "F" => self.unary()?.finally(),
+                "G" => self.unary()?.globally(),
+                "U" | "R" | "V" | "W" => bail!("binary operator '{name}' is missing an operand"),
+                _ => Ltl::Prop(name),
+            },
+            other => bail!("unexpected token '{other}' in LTL formula"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ltl() {
+        let p = || Ltl::from("p");
+        let q = || Ltl::from("q");
+        let r = || Ltl::from("r");
+
+        assert_eq!(
+            Ltl::parse("G F !p").unwrap(),
+            p().not().finally().globally()
+        );
+        assert_eq!(Ltl::parse("[]<>p").unwrap(), p().finally().globally());
+        assert_eq!(Ltl::parse("p && q || r").unwrap(), p().and(q()).or(r()));
+        assert_eq!(
+            Ltl::parse("p -> q -> r").unwrap(),
+            p().implies(q().implies(r()))
+        );
+        assert_eq!(Ltl::parse("p U q && r").unwrap(), p().until(q()).and(r()));
+        assert_eq!(
+            Ltl::parse("G ( p -> ! X q )").unwrap(),
+            p().implies(q().next().not()).globally()
+        );
+        assert_eq!(Ltl::parse("p V q").unwrap(), p().release(q()));
+
+        assert!(Ltl::parse("p &&").is_err());
+        assert!(Ltl::parse("(p || q").is_err());
+        assert!(Ltl::parse("p q").is_err());
+    }
+
+    #[test]
+    fn test_ltl_display_roundtrip() {
+        let ltl = Ltl::parse("G ((is2 && X is8) -> G F is11) && (a W b) <-> !c R d").unwrap();
+        assert_eq!(Ltl::parse(&ltl.to_string()).unwrap(), ltl);
+    }
+
+    #[test]
+    fn test_nnf() {
+        let nnf = Ltl::parse("!(p U G q)").unwrap().nnf();
+        assert_eq!(nnf.to_string(), "(!p R (true U !q))");
+
+        let nnf = Ltl::parse("!(p -> X q)").unwrap().nnf();
+        assert_eq!(nnf.to_string(), "(p && X !q)");
+    }
+}
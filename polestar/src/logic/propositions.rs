@@ -1,12 +1,10 @@
 //! A mechanism for working with LTL formulae.
 //!
-//! This module is the simplest thing I could cobble together to get LTL-to-Buchi
-//! working. It's not a great long-term solution, but it's a start.
-//! Eventually, this will be replaced by a native LTL-to-Buchi implementation,
-//! rather than using `ltl3ba` as a command-line tool, which will allow working
-//! with Proposition types directly and removing the need for an intermediate
-//! string representation of LTL formulae. There will also be some macros for
-//! expressing propositions within LTL statements directly.
+//! LTL formulae refer to atomic propositions by name, so this module provides
+//! a way to map those names back to your own Proposition types, which can be
+//! evaluated against the transitions of your model.
+//! Formulae can be written as strings and parsed (see [`Ltl::parse`]), or built
+//! up directly as [`Ltl`] values.
 //!
 //! See this example to understand how the different parts fit together:
 //!
@@ -73,9 +71,7 @@ pub trait EvaluatePropositions<P> {
 
 /// A registry for atomic propositions, used to build up LTL formulae.
 ///
-/// This mechanism is mostly a necessity because polestar leans on an external command-line tool,
-/// `ltl3ba`, for incorporating LTL formulae into [`crate::model_checker::ModelChecker`],
-/// and so the input must be passed as a string.
+/// LTL formulae name their atomic propositions with strings.
 /// The `PropositionRegistry` provides a relatively easy way to map between a custom type `P`
 /// representing propositions, and strings which are used in an LTL formula.
 ///
@@ -105,7 +101,7 @@ where
     }
 
     /// Create a [`PropositionRegistry`] pre-populated with a list of [`P`]s.
-    pub fn new<'a, T: Into<P>>(ps: impl IntoIterator<Item = T>) -> Result<Self, String> {
+    pub fn new<T: Into<P>>(ps: impl IntoIterator<Item = T>) -> Result<Self, String> {
         let mut props = Self::empty();
 
         for p in ps {
@@ -128,12 +124,12 @@ where
             name
         };
 
-        if let Some(old) = self.0.insert(name.clone(), p.clone()) {
-            if old != p {
-                return Err(format!(
-                    "Attempted to add to propmap with name collision: {disp} -> {name}"
-                ));
-            }
+        if let Some(old) = self.0.insert(name.clone(), p.clone())
+            && old != p
+        {
+            return Err(format!(
+                "Attempted to add to propmap with name collision: {disp} -> {name}"
+            ));
         }
         Ok(name)
     }
@@ -168,7 +164,7 @@ where
     P: PropositionMapping,
 {
     pub(crate) props: &'p P,
-    pub(crate) transition: Transition<M>,
+    pub(crate) transition: &'p Transition<M>,
 }
 
 impl<M, P> EvaluatePropositions<String> for PropositionBindings<'_, M, P>
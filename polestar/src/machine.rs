@@ -55,6 +55,17 @@ where
     /// Defines the transition function of the machine.
     fn transition(&self, state: Self::State, action: Self::Action) -> TransitionResult<Self>;
 
+    /// Lists the actions to try in this state, which must include every action
+    /// that can be taken in it. Listing actions which fail is allowed, but wasteful.
+    ///
+    /// By default, this is `None`, which means that every possible action is tried
+    /// in every state, as enumerated by [`Exhaustive`], which is wasteful when few
+    /// actions are enabled in each state, and impossible for action types which are
+    /// not `Exhaustive` or too large to enumerate.
+    fn enabled_actions(&self, _: &Self::State) -> Option<impl Iterator<Item = Self::Action>> {
+        None::<std::iter::Empty<_>>
+    }
+
     /// Designates this state as a terminal state.
     ///
     /// This is an optional hint, useful for generating diagrams from FSMs.
@@ -75,6 +86,7 @@ where
     }
 
     /// Perform a sequence of transitions, collecting the list of effects and returning the final state.
+    #[allow(clippy::type_complexity)]
     fn transitions(
         &self,
         mut state: Self::State,
@@ -100,6 +112,7 @@ where
 
     /// Apply a sequence of actions to a state, causing a transition for each one,
     /// with a callback function called after each transition.
+    #[allow(clippy::type_complexity)]
     fn apply_each_action(
         &self,
         mut state: Self::State,
@@ -124,6 +137,7 @@ where
 
     /// Apply a sequence of actions to a state, causing a transition for each one,
     /// collecting the list of effects and returning the final state.
+    #[allow(clippy::type_complexity)]
     fn apply_actions(
         &self,
         state: Self::State,
@@ -137,6 +151,7 @@ where
     }
 
     /// Apply actions but throw away the effects.
+    #[allow(clippy::type_complexity)]
     fn apply_actions_(
         &self,
         state: Self::State,
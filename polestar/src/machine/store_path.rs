@@ -9,6 +9,14 @@ use derive_more::derive::Deref;
 
 /// The state for [`StorePathMachine`]
 #[derive(Deref, derive_more::Debug)]
+#[cfg_attr(
+    feature = "recording",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "S: serde::Serialize, A: Clone + serde::Serialize",
+        deserialize = "S: serde::Deserialize<'de>, A: Clone + serde::Deserialize<'de>"
+    ))
+)]
 pub struct StorePathState<S, A>
 where
     S: Debug,
@@ -98,6 +106,10 @@ where
         Ok((state, fx))
     }
 
+    fn enabled_actions(&self, state: &Self::State) -> Option<impl Iterator<Item = Self::Action>> {
+        self.machine.enabled_actions(&state.state)
+    }
+
     fn is_terminal(&self, state: &Self::State) -> bool {
         self.machine.is_terminal(&state.state)
     }
@@ -45,6 +45,7 @@ mod tests {
 
     #[test]
     fn test_state_model() {
+        #[allow(unused)]
         #[derive(Debug, Clone)]
         struct Adder {
             sum: u32,
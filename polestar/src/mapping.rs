@@ -42,7 +42,9 @@ use crate::prelude::*;
 /// and then using the model's transition function to obtain `Q'`.
 ///
 /// Upholding this invariant ensures that the model is properly tracking the system state
-/// through its transitions.
+/// through its transitions. When the system is itself a [`Machine`], the invariant can be
+/// checked for every reachable transition with
+/// [`Traversal::check_refinement`](crate::traversal::Traversal::check_refinement).
 ///
 pub trait ModelMapping
 where
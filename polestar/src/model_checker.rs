@@ -2,15 +2,17 @@
 //! which represents a set of safety and liveness specifications.
 
 pub mod buchi;
+pub(crate) mod liveness;
+mod tableau;
 
 #[cfg(test)]
 mod tests;
 
-use std::{fmt::Debug, hash::Hash};
+use std::{fmt::Debug, hash::Hash, sync::Arc};
 
 use buchi::*;
 
-use crate::logic::{EvaluatePropositions, PropositionMapping, Transition};
+use crate::logic::{EvaluatePropositions, Ltl, PropositionMapping, Transition};
 use crate::machine::{
     store_path::{StorePathMachine, StorePathState},
     Machine, TransitionResult,
@@ -18,16 +20,62 @@ use crate::machine::{
 
 /// A model checker which connects a state machine with a Buchi automaton
 /// to check a set of safety and liveness specifications.
+///
+/// Safety violations are caught as the state space is traversed, by tracking the
+/// automaton for the specification. Liveness is checked after the traversal, by
+/// searching for a cycle accepted by the automaton for the negated specification.
 pub struct ModelChecker<M, P>
 where
     M: Machine,
     P: PropositionMapping,
 {
     buchi: BuchiAutomaton<M, P>,
+    pub(crate) negation: Arc<BuchiAutomaton<M, P>>,
+    pub(crate) fairness: Vec<Fairness<M::Action>>,
     machine: StorePathMachine<M>,
 }
 
-/// Model checkers can fail due to either safety or liveness violations.
+/// A fairness assumption about a class of actions, given by a predicate.
+///
+/// Without fairness, a liveness property can fail just because the model is allowed to
+/// ignore some action forever, even though that action would always be taken eventually
+/// in the real system. Liveness checking only considers paths which are fair
+/// with respect to every assumption.
+#[derive(Clone)]
+pub enum Fairness<A> {
+    /// If some matching action is continuously enabled from some point on,
+    /// then some matching action is eventually taken.
+    Weak(Arc<dyn Fn(&A) -> bool + Send + Sync>),
+    /// If some matching action is enabled infinitely often,
+    /// then some matching action is taken infinitely often.
+    Strong(Arc<dyn Fn(&A) -> bool + Send + Sync>),
+}
+
+impl<A> Fairness<A> {
+    /// Weak fairness for the actions matching the predicate.
+    pub fn weak(actions: impl Fn(&A) -> bool + Send + Sync + 'static) -> Self {
+        Self::Weak(Arc::new(actions))
+    }
+
+    /// Strong fairness for the actions matching the predicate.
+    pub fn strong(actions: impl Fn(&A) -> bool + Send + Sync + 'static) -> Self {
+        Self::Strong(Arc::new(actions))
+    }
+
+    /// Whether the action belongs to the class of actions this assumption is about.
+    pub fn matches(&self, action: &A) -> bool {
+        match self {
+            Self::Weak(f) | Self::Strong(f) => f(action),
+        }
+    }
+
+    /// Whether this is a weak fairness assumption.
+    pub fn is_weak(&self) -> bool {
+        matches!(self, Self::Weak(_))
+    }
+}
+
+/// Model checkers can fail due to safety or liveness violations, or deadlocks.
 #[derive(derive_bounded::Debug)]
 #[bounded_to(M::State, M::Action)]
 pub enum ModelCheckerError<M: Machine>
@@ -43,11 +91,32 @@ where
     },
     /// A liveness violation occurred, meaning that something that was supposed to happen never did.
     Liveness {
-        /// All paths that lead to the loop which causes the liveness violation..
-        paths: Vec<im::Vector<M::Action>>,
+        /// An infinite path which violates the specification.
+        lasso: Lasso<M::State, M::Action>,
+    },
+    /// A deadlock was reached: a state which is not terminal, but in which no action
+    /// can be taken (see [`Traversal::check_deadlock`](crate::traversal::Traversal::check_deadlock)).
+    Deadlock {
+        /// The state the path starts from.
+        initial: M::State,
+        /// The steps leading from the initial state to the deadlock, which is the last one,
+        /// or the initial state if there are none.
+        path: Vec<(M::Action, M::State)>,
     },
 }
 
+/// An infinite path through a state machine, in the form of a finite prefix
+/// followed by a cycle which repeats forever.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lasso<S, A> {
+    /// The state the path starts from.
+    pub initial: S,
+    /// The steps leading from the initial state to the start of the cycle.
+    pub prefix: Vec<(A, S)>,
+    /// The steps leading from the start of the cycle back around to itself.
+    pub cycle: Vec<(A, S)>,
+}
+
 /*                                   █████       ███
                                    ░░███       ░░░
  █████████████    ██████    ██████  ░███████   ████  ████████    ██████
@@ -76,6 +145,7 @@ where
         let ModelCheckerState {
             pathstate: state,
             buchi,
+            valuation: _,
         } = state;
 
         let prev = state.state.clone();
@@ -85,24 +155,28 @@ where
             .transition(state.clone(), action.clone())
             .map_err(ModelCheckerTransitionError::MachineError)?;
 
-        let buchi_next = self
-            .buchi
-            .transition_(buchi, Transition(prev.clone(), action, next.state.clone()))
-            .map_err(|error| {
-                ModelCheckerTransitionError::BuchiError(ModelCheckerBuchiError {
-                    error,
-                    path: next.path.clone(),
-                    states: (prev, next.state.clone()),
-                })
-            })?;
+        let transition = Transition(prev.clone(), action, next.state.clone());
+        let valuation = self.negation.valuation(&transition);
+        let buchi_next = self.buchi.transition_(buchi, transition).map_err(|error| {
+            ModelCheckerTransitionError::BuchiError(ModelCheckerBuchiError {
+                error,
+                path: next.path.clone(),
+                states: (prev, next.state.clone()),
+            })
+        })?;
 
         let next = ModelCheckerState {
             pathstate: next,
             buchi: buchi_next,
+            valuation,
         };
         Ok((next, fx))
     }
 
+    fn enabled_actions(&self, state: &Self::State) -> Option<impl Iterator<Item = Self::Action>> {
+        self.machine.enabled_actions(&state.pathstate)
+    }
+
     fn is_terminal(&self, state: &Self::State) -> bool {
         self.machine.is_terminal(&state.pathstate)
     }
@@ -118,9 +192,38 @@ where
     /// Create a model checker from a state machine, a proposition name mapping,
     /// and an LTL formula.
     pub fn from_ltl(machine: M, propmap: P, ltl: &str) -> anyhow::Result<Self> {
-        let buchi = BuchiAutomaton::from_ltl(propmap, ltl)?;
+        Ok(Self::from_formula(machine, propmap, &Ltl::parse(ltl)?))
+    }
+
+    /// Create a model checker from a state machine, a proposition name mapping,
+    /// and an already-parsed LTL formula.
+    pub fn from_formula(machine: M, propmap: P, ltl: &Ltl) -> Self {
+        let propmap = Arc::new(propmap);
+        Self {
+            buchi: BuchiAutomaton::from_formula(propmap.clone(), ltl),
+            negation: Arc::new(BuchiAutomaton::from_formula(propmap, &ltl.clone().not())),
+            fairness: vec![],
+            machine: StorePathMachine::from(machine),
+        }
+    }
+
+    /// Add a fairness assumption, to be used when checking liveness.
+    pub fn with_fairness(mut self, fairness: Fairness<M::Action>) -> Self {
+        self.fairness.push(fairness);
+        self
+    }
+
+    /// Like [`ModelChecker::from_ltl`], but uses the external `ltl3ba` tool
+    /// to build the Buchi automata.
+    #[cfg(feature = "ltl3ba")]
+    pub fn from_ltl3ba(machine: M, propmap: P, ltl: &str) -> anyhow::Result<Self> {
+        let propmap = Arc::new(propmap);
+        let buchi = BuchiAutomaton::from_ltl3ba(propmap.clone(), ltl)?;
+        let negation = BuchiAutomaton::from_ltl3ba(propmap, &format!("!({ltl})"))?;
         Ok(Self {
             buchi,
+            negation: Arc::new(negation),
+            fairness: vec![],
             machine: StorePathMachine::from(machine),
         })
     }
@@ -128,14 +231,7 @@ where
     /// Given a model's state, return an initial state for the model checker which
     /// corresponds with the model's initial state.
     pub fn initial(&self, state: M::State) -> ModelCheckerState<M::State, M::Action> {
-        let inits = self
-            .buchi
-            .states
-            .keys()
-            .filter(|&name| name.ends_with("_init"))
-            .cloned();
-
-        ModelCheckerState::new(state, inits)
+        ModelCheckerState::new(state, self.buchi.initial_states().cloned())
     }
 }
 
@@ -177,13 +273,8 @@ where
 ░░░░░░     ░░░░░   ░░░░░░░░    ░░░░░   ░░░░░░  */
 
 /// The State used in the [`ModelChecker`]
-#[derive(
-    derive_more::Debug,
-    derive_bounded::Clone,
-    derive_bounded::PartialEq,
-    derive_bounded::Eq,
-    derive_more::Deref,
-)]
+#[derive(derive_more::Debug, derive_bounded::Clone, derive_more::Deref)]
+#[cfg_attr(feature = "recording", derive(serde::Serialize, serde::Deserialize))]
 #[bounded_to(S, A)]
 pub struct ModelCheckerState<S, A>
 where
@@ -200,6 +291,29 @@ where
     /// all possible paths, of which there may be many at once.
     #[debug(skip)]
     pub(crate) buchi: BuchiStateNames,
+
+    /// The truth values of the propositions for the transition which led to this state,
+    /// used for checking liveness after the traversal.
+    #[debug(skip)]
+    pub(crate) valuation: Valuation,
+}
+
+// XXX: equality and hash ignore the valuation, which depends on how the state was reached.
+impl<S, A> PartialEq for ModelCheckerState<S, A>
+where
+    S: Clone + Debug + Eq + Hash,
+    A: Clone + Debug,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.pathstate == other.pathstate && self.buchi == other.buchi
+    }
+}
+
+impl<S, A> Eq for ModelCheckerState<S, A>
+where
+    S: Clone + Debug + Eq + Hash,
+    A: Clone + Debug,
+{
 }
 
 // NB: regrettably we can't easily derive Hash because ModelChecker is not Hash,
@@ -226,6 +340,7 @@ where
         Self {
             pathstate: StorePathState::new(state),
             buchi: BuchiStateNames(buchi_states.into_iter().collect()),
+            valuation: Valuation::default(),
         }
     }
 
@@ -240,6 +355,7 @@ where
         Some(ModelCheckerState {
             pathstate,
             buchi: self.buchi,
+            valuation: self.valuation,
         })
     }
 }
@@ -4,25 +4,31 @@ use std::{
     collections::{BTreeSet, HashMap},
     fmt::Debug,
     marker::PhantomData,
-    process::Command,
     sync::Arc,
 };
 
 use anyhow::anyhow;
+use itertools::Itertools;
 
 use crate::{
     logic::{
-        EvaluatePropositions, LogicStatement, PropositionBindings, PropositionMapping, Transition,
+        EvaluatePropositions, LogicStatement, Ltl, PropositionBindings, PropositionMapping,
+        Transition,
     },
     Machine, TransitionResult,
 };
 
+use super::tableau;
+
 #[derive(derive_more::Debug)]
 pub(crate) struct BuchiAutomaton<M: Machine, PM: PropositionMapping> {
     pub states: HashMap<StateName, Arc<BuchiState>>,
 
+    /// All propositions referred to by the automaton, sorted
+    #[debug(skip)]
+    propositions: Vec<String>,
     #[debug(skip)]
-    propmap: PM,
+    propmap: Arc<PM>,
     #[debug(skip)]
     phantom: PhantomData<M>,
 }
@@ -44,8 +50,8 @@ where
         inner_transition: Self::Action,
     ) -> TransitionResult<Self> {
         let props = PropositionBindings {
-            props: &self.propmap,
-            transition: inner_transition,
+            props: &*self.propmap,
+            transition: &inner_transition,
         };
         let next = state
             .0
@@ -61,7 +67,8 @@ where
                 //     ))
                 // })?;
                 match &**buchi_state {
-                    BuchiState::Skip => BTreeSet::new(), // vec![Ok((buchi_name, buchi_state))],
+                    // a `skip` never claim accepts everything from here on
+                    BuchiState::Skip => BTreeSet::from([buchi_name]),
                     BuchiState::Conditional { predicates, .. } => predicates
                         .iter()
                         .filter_map(|(ltl, name)| ltl.eval(&props).then_some(name))
@@ -92,11 +99,86 @@ pub enum BuchiError {
 }
 
 impl<M: Machine, PM: PropositionMapping> BuchiAutomaton<M, PM> {
-    pub fn from_ltl(propmap: PM, ltl_str: &str) -> Result<Self, anyhow::Error> {
-        let output = Command::new("ltl3ba")
+    /// Build the automaton from an LTL formula.
+    pub fn from_formula(propmap: Arc<PM>, ltl: &Ltl) -> Self {
+        Self::from_states(propmap, tableau::translate(ltl))
+    }
+
+    fn from_states(propmap: Arc<PM>, states: HashMap<StateName, Arc<BuchiState>>) -> Self {
+        let mut propositions = BTreeSet::new();
+        for state in states.values() {
+            if let BuchiState::Conditional { predicates, .. } = &**state {
+                for (predicate, _) in predicates {
+                    collect_propositions(predicate, &mut propositions);
+                }
+            }
+        }
+        Self {
+            states,
+            propositions: propositions.into_iter().collect(),
+            propmap,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The names of the initial states, in order.
+    pub fn initial_states(&self) -> impl Iterator<Item = &StateName> {
+        self.states
+            .keys()
+            .filter(|name| name.ends_with("_init"))
+            .sorted()
+    }
+
+    /// Whether the named state is accepting.
+    pub fn is_accepting(&self, name: &StateName) -> bool {
+        self.states[name].is_accepting()
+    }
+
+    /// Evaluate all of the automaton's propositions for a transition of the model,
+    /// so that the automaton can be stepped later without the model at hand.
+    pub fn valuation(&self, transition: &Transition<M>) -> Valuation
+    where
+        Transition<M>: EvaluatePropositions<PM::Proposition>,
+    {
+        let props = PropositionBindings {
+            props: &*self.propmap,
+            transition,
+        };
+        Valuation(
+            self.propositions
+                .iter()
+                .map(|p| props.evaluate(p))
+                .collect(),
+        )
+    }
+
+    /// The states which can follow the named state, given the valuation of a transition.
+    pub fn successors<'a>(
+        &'a self,
+        name: &'a StateName,
+        valuation: &Valuation,
+    ) -> Vec<&'a StateName> {
+        let props = ValuationBindings {
+            propositions: &self.propositions,
+            valuation,
+        };
+        match &*self.states[name] {
+            BuchiState::Skip => vec![name],
+            BuchiState::Conditional { predicates, .. } => predicates
+                .iter()
+                .filter_map(|(predicate, next)| predicate.eval(&props).then_some(next))
+                .collect(),
+        }
+    }
+
+    /// Build the automaton by shelling out to the `ltl3ba` command-line tool,
+    /// which must be installed.
+    #[cfg(feature = "ltl3ba")]
+    pub fn from_ltl3ba(propmap: Arc<PM>, ltl_str: &str) -> Result<Self, anyhow::Error> {
+        let output = std::process::Command::new("ltl3ba")
             .args(["-f", ltl_str])
             .output()
-            .unwrap();
+            .map_err(|e| anyhow!("couldn't run ltl3ba, is it installed? Error: {e}"))?;
 
         let promela = String::from_utf8_lossy(&output.stdout);
 
@@ -107,7 +189,8 @@ impl<M: Machine, PM: PropositionMapping> BuchiAutomaton<M, PM> {
         Ok(Self::from_promela(propmap, &promela))
     }
 
-    pub fn from_promela(propmap: PM, promela: &str) -> Self {
+    #[cfg(feature = "ltl3ba")]
+    pub fn from_promela(propmap: Arc<PM>, promela: &str) -> Self {
         let lines = promela.lines().collect::<Vec<_>>();
 
         let pat_state = regex::Regex::new("^(\\w+):").unwrap();
@@ -151,47 +234,76 @@ impl<M: Machine, PM: PropositionMapping> BuchiAutomaton<M, PM> {
         let (state_name, state) = current.unwrap();
         states.insert(state_name, Arc::new(state));
 
-        Self {
-            states,
-            propmap,
-            phantom: PhantomData,
+        Self::from_states(propmap, states)
+    }
+}
+
+fn collect_propositions(statement: &LogicStatement, props: &mut BTreeSet<String>) {
+    match statement {
+        LogicStatement::True | LogicStatement::False => {}
+        LogicStatement::Prop(name) => {
+            props.insert(name.clone());
+        }
+        LogicStatement::Not(p) => collect_propositions(p, props),
+        LogicStatement::And(p1, p2)
+        | LogicStatement::Or(p1, p2)
+        | LogicStatement::Implies(p1, p2) => {
+            collect_propositions(p1, props);
+            collect_propositions(p2, props);
         }
     }
 }
 
-pub(crate) type StateName = String;
+/// The truth value of each of an automaton's propositions, for one transition of the model.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "recording", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Valuation(Vec<bool>);
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, derive_more::Deref, derive_more::From)]
-pub(crate) struct BuchiStateNames(pub(crate) BTreeSet<StateName>);
+struct ValuationBindings<'a> {
+    propositions: &'a [String],
+    valuation: &'a Valuation,
+}
 
-impl BuchiStateNames {
-    pub fn is_accepting(&self) -> bool {
-        self.iter().any(|n| n.starts_with("accept_"))
+impl EvaluatePropositions<String> for ValuationBindings<'_> {
+    fn evaluate(&self, prop: &String) -> bool {
+        let i = self
+            .propositions
+            .binary_search(prop)
+            .unwrap_or_else(|_| panic!("no valuation for prop: {prop}"));
+        self.valuation.0[i]
     }
 }
 
+pub(crate) type StateName = String;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, derive_more::Deref, derive_more::From)]
+#[cfg_attr(feature = "recording", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct BuchiStateNames(pub(crate) BTreeSet<StateName>);
+
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub(crate) enum BuchiState {
     Conditional {
         accepting: bool,
         predicates: Vec<(LogicStatement, StateName)>,
     },
+    /// Only produced by `ltl3ba`
+    #[cfg_attr(not(feature = "ltl3ba"), allow(dead_code))]
     Skip,
 }
 
-// impl BuchiState {
-//     /// Is this an accepting state?
-//     ///
-//     /// A path through a Buchi automaton is accepted if the path always eventually
-//     /// passes through an accepting state.
-//     /// (see https://en.wikipedia.org/wiki/B%C3%BCchi_automaton)
-//     pub fn is_accepting(&self) -> bool {
-//         match self {
-//             BuchiState::Skip => true,
-//             BuchiState::Conditional { accepting, .. } => *accepting,
-//         }
-//     }
-// }
+impl BuchiState {
+    /// Is this an accepting state?
+    ///
+    /// A path through a Buchi automaton is accepted if the path always eventually
+    /// passes through an accepting state.
+    /// (see https://en.wikipedia.org/wiki/B%C3%BCchi_automaton)
+    pub fn is_accepting(&self) -> bool {
+        match self {
+            BuchiState::Skip => true,
+            BuchiState::Conditional { accepting, .. } => *accepting,
+        }
+    }
+}
 
 impl Debug for BuchiState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -217,7 +329,7 @@ impl Debug for BuchiState {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "ltl3ba"))]
 mod tests {
     use crate::logic::PropositionRegistry;
 
@@ -296,8 +408,42 @@ accept_S10:
 }
         "#;
         let propmap = PropositionRegistry::<String>::new(["open", "call", "at_floor"]).unwrap();
-        let machine =
-            BuchiAutomaton::<(), PropositionRegistry<String>>::from_promela(propmap, promela);
+        let machine = BuchiAutomaton::<(), PropositionRegistry<String>>::from_promela(
+            Arc::new(propmap),
+            promela,
+        );
         dbg!(&machine);
     }
+
+    impl EvaluatePropositions<String> for Transition<()> {
+        fn evaluate(&self, _: &String) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn from_promela_skip() {
+        // ltl3ba's never claim for a formula which always holds
+        let promela = r#"
+never { /* true */
+accept_init:
+	skip
+}
+        "#;
+        let propmap = PropositionRegistry::<String>::new(["p"]).unwrap();
+        let machine = BuchiAutomaton::<(), PropositionRegistry<String>>::from_promela(
+            Arc::new(propmap),
+            promela,
+        );
+        let init = "accept_init".to_string();
+        assert!(machine.is_accepting(&init));
+        assert_eq!(machine.successors(&init, &Valuation::default()), [&init]);
+
+        // Every transition is accepted, forever
+        let state = BuchiStateNames(BTreeSet::from([init]));
+        let next = machine
+            .transition_(state.clone(), Transition((), (), ()))
+            .unwrap();
+        assert_eq!(next, state);
+    }
 }
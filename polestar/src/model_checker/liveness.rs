@@ -0,0 +1,269 @@
+//! Liveness checking, by searching for an accepting cycle in the product of a
+//! traversed state graph with a Buchi automaton for the negated specification.
+//!
+//! Any such cycle is an infinite path which the model can take, along which
+//! the specification is never satisfied.
+
+use std::{
+    collections::{hash_map::Entry, BTreeSet, HashMap, VecDeque},
+    hash::Hash,
+};
+
+use petgraph::{
+    graph::{DiGraph, EdgeIndex, EdgeReference, NodeIndex},
+    visit::{EdgeRef, NodeFiltered},
+};
+
+use crate::{logic::PropositionMapping, Machine};
+
+use super::{
+    buchi::{BuchiAutomaton, StateName, Valuation},
+    Fairness, Lasso,
+};
+
+/// An edge of the state graph, as recorded for liveness checking.
+#[derive(
+    derive_bounded::Clone, derive_bounded::Debug, derive_bounded::PartialEq, derive_bounded::Eq,
+)]
+#[bounded_to(M::State, M::Action)]
+pub(crate) struct LivenessEdge<M: Machine> {
+    pub action: M::Action,
+    /// The state which the edge leads to
+    pub next: M::State,
+    pub valuation: Valuation,
+}
+
+impl<M: Machine> Hash for LivenessEdge<M>
+where
+    M::State: Hash,
+    M::Action: Hash,
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.action.hash(state);
+        self.next.hash(state);
+        self.valuation.hash(state);
+    }
+}
+
+/// A node of the product graph: a node of the state graph paired with an automaton state
+type ProductNode<'a> = (NodeIndex, &'a StateName);
+
+/// Search the product of the state graph and the automaton for a reachable cycle
+/// which passes through an accepting state, and which is fair with respect to each of the
+/// fairness assumptions. Returns the path to the closest such cycle.
+///
+/// `initial` pairs the graph node of each initial state with the state itself.
+///
+/// Fair cycles are found by SCC decomposition: an SCC which contains an accepting state
+/// contains a fair accepting cycle if each weakly fair class of actions is either
+/// disabled somewhere or taken somewhere within it, and each strongly fair class is
+/// either never enabled or taken somewhere within it. If a strongly fair class is only
+/// enabled but never taken, the states where it is enabled are removed, and the
+/// remainder of the SCC is searched again.
+pub(crate) fn find_lasso<M, P, N>(
+    negation: &BuchiAutomaton<M, P>,
+    graph: &DiGraph<N, LivenessEdge<M>>,
+    initial: &[(NodeIndex, M::State)],
+    fairness: &[Fairness<M::Action>],
+) -> Option<Lasso<M::State, M::Action>>
+where
+    M: Machine,
+    M::State: Clone,
+    M::Action: Clone,
+    P: PropositionMapping,
+{
+    // Build the reachable part of the product breadth-first, so that the
+    // recorded parents give shortest paths from the initial nodes, and nodes
+    // are numbered in order of their distance from the initial nodes.
+    let mut product: DiGraph<ProductNode, EdgeIndex> = DiGraph::new();
+    let mut ids: HashMap<ProductNode, NodeIndex> = HashMap::new();
+    let mut parents: HashMap<NodeIndex, (NodeIndex, EdgeIndex)> = HashMap::new();
+    let mut origins: HashMap<NodeIndex, &M::State> = HashMap::new();
+    let mut queue = VecDeque::new();
+
+    for (node, state) in initial {
+        for q in negation.initial_states() {
+            if let Entry::Vacant(entry) = ids.entry((*node, q)) {
+                let ix = product.add_node((*node, q));
+                entry.insert(ix);
+                origins.insert(ix, state);
+                queue.push_back(ix);
+            }
+        }
+    }
+
+    while let Some(ix) = queue.pop_front() {
+        let (node, q) = product[ix];
+        for edge in graph.edges(node) {
+            for next_q in negation.successors(q, &edge.weight().valuation) {
+                let key = (edge.target(), next_q);
+                let next_ix = match ids.get(&key) {
+                    Some(&next_ix) => next_ix,
+                    None => {
+                        let next_ix = product.add_node(key);
+                        ids.insert(key, next_ix);
+                        parents.insert(next_ix, (ix, edge.id()));
+                        queue.push_back(next_ix);
+                        next_ix
+                    }
+                };
+                product.add_edge(ix, next_ix, edge.id());
+            }
+        }
+    }
+
+    let takes = |e: EdgeIndex, fair: &Fairness<M::Action>| fair.matches(&graph[e].action);
+    // Whether a class of actions is enabled at a product node
+    let enabled = |ix: NodeIndex, fair: &Fairness<M::Action>| {
+        graph
+            .edges(product[ix].0)
+            .any(|edge| fair.matches(&edge.weight().action))
+    };
+
+    // Find the fair SCC whose accepting state is closest to the initial states.
+    // SCCs are kept as ordered sets, so that the same lasso is found on every run.
+    let mut best: Option<(BTreeSet<NodeIndex>, NodeIndex)> = None;
+    let mut stack = petgraph::algo::tarjan_scc(&product);
+    while let Some(scc) = stack.pop() {
+        if scc.len() == 1 && !product.contains_edge(scc[0], scc[0]) {
+            continue;
+        }
+        let in_scc: BTreeSet<NodeIndex> = scc.iter().copied().collect();
+        let internal = || internal_edges(&product, &in_scc);
+        let Some(start) = scc
+            .iter()
+            .copied()
+            .filter(|&ix| negation.is_accepting(product[ix].1))
+            .min()
+        else {
+            continue;
+        };
+        let taken = |fair| internal().any(|e| takes(*e.weight(), fair));
+
+        let weakly_unfair = fairness
+            .iter()
+            .any(|fair| fair.is_weak() && scc.iter().all(|&ix| enabled(ix, fair)) && !taken(fair));
+        if weakly_unfair {
+            continue;
+        }
+
+        let strongly_unfair: Vec<_> = fairness
+            .iter()
+            .filter(|fair| !fair.is_weak())
+            .filter(|fair| scc.iter().any(|&ix| enabled(ix, fair)) && !taken(fair))
+            .collect();
+        if strongly_unfair.is_empty() {
+            if best.as_ref().is_none_or(|(_, best)| start < *best) {
+                best = Some((in_scc, start));
+            }
+        } else {
+            let remainder: BTreeSet<NodeIndex> = scc
+                .iter()
+                .copied()
+                .filter(|&ix| strongly_unfair.iter().all(|fair| !enabled(ix, fair)))
+                .collect();
+            let subgraph = NodeFiltered::from_fn(&product, |ix| remainder.contains(&ix));
+            stack.extend(petgraph::algo::tarjan_scc(&subgraph));
+        }
+    }
+    let (in_scc, start) = best?;
+
+    let step = |e: EdgeIndex| {
+        let edge = &graph[e];
+        (edge.action.clone(), edge.next.clone())
+    };
+
+    let mut prefix = vec![];
+    let mut ix = start;
+    while let Some(&(parent, e)) = parents.get(&ix) {
+        prefix.push(step(e));
+        ix = parent;
+    }
+    prefix.reverse();
+    let initial = origins[&ix].clone();
+
+    // Build a cycle from the accepting state which satisfies every fairness assumption,
+    // by visiting in turn a state where each class of actions is disabled,
+    // or else an edge where it is taken.
+    let mut waypoints = vec![];
+    for fair in fairness {
+        let mut edges = internal_edges(&product, &in_scc);
+        if let Some(&ix) = in_scc.iter().find(|&&ix| !enabled(ix, fair)) {
+            waypoints.push((ix, None));
+        } else if let Some(e) = edges.find(|e| takes(*e.weight(), fair)) {
+            waypoints.push((e.source(), Some(e.id())));
+        }
+    }
+
+    let mut cycle = vec![];
+    let mut ix = start;
+    for (node, edge) in waypoints {
+        cycle.extend(path_within(&product, &in_scc, ix, node, true));
+        ix = node;
+        if let Some(e) = edge {
+            cycle.push(product[e]);
+            ix = product.edge_endpoints(e).unwrap().1;
+        }
+    }
+    cycle.extend(path_within(&product, &in_scc, ix, start, !cycle.is_empty()));
+
+    Some(Lasso {
+        initial,
+        prefix,
+        cycle: cycle.into_iter().map(step).collect(),
+    })
+}
+
+/// The edges of the product which lie within the given set of nodes.
+fn internal_edges<'a>(
+    product: &'a DiGraph<ProductNode, EdgeIndex>,
+    within: &'a BTreeSet<NodeIndex>,
+) -> impl Iterator<Item = EdgeReference<'a, EdgeIndex>> {
+    within
+        .iter()
+        .flat_map(|&ix| product.edges(ix))
+        .filter(|e| within.contains(&e.target()))
+}
+
+/// The shortest path between two nodes of the product which stays within the given set,
+/// as the edges of the state graph which it follows.
+/// If `from` and `to` are the same, the path may be empty only if `allow_empty` is set.
+fn path_within(
+    product: &DiGraph<ProductNode, EdgeIndex>,
+    within: &BTreeSet<NodeIndex>,
+    from: NodeIndex,
+    to: NodeIndex,
+    allow_empty: bool,
+) -> Vec<EdgeIndex> {
+    if from == to && allow_empty {
+        return vec![];
+    }
+    let mut parents: HashMap<NodeIndex, (NodeIndex, EdgeIndex)> = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    'search: while let Some(ix) = queue.pop_front() {
+        for edge in product.edges(ix) {
+            let next = edge.target();
+            if !within.contains(&next) || parents.contains_key(&next) {
+                continue;
+            }
+            parents.insert(next, (ix, *edge.weight()));
+            if next == to {
+                break 'search;
+            }
+            queue.push_back(next);
+        }
+    }
+
+    let mut path = vec![];
+    let mut ix = to;
+    loop {
+        let (parent, e) = parents[&ix];
+        path.push(e);
+        ix = parent;
+        if ix == from {
+            break;
+        }
+    }
+    path.reverse();
+    path
+}
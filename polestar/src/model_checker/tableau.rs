@@ -0,0 +1,540 @@
+//! Native translation of LTL formulae into Buchi automata.
+//!
+//! This is a tableau construction in the spirit of Gerth, Peled, Vardi and Wolper
+//! ("Simple On-the-fly Automatic Verification of Linear Temporal Logic", 1995).
+//! Each state of the automaton is a set of obligations: formulas which must hold
+//! from that point on. A state is expanded into its outgoing edges by computing the
+//! ways ("covers") in which its obligations can be satisfied, each consisting of
+//! a propositional label for the current step and a set of obligations for the next.
+//!
+//! A few departures from the paper keep the automaton small enough to be practical:
+//! - purely propositional subformulae are not split into cases, but are kept whole
+//!   as labels, which suits the common `G (boolean stuff)` kind of spec
+//! - labels live on edges rather than states, so they don't multiply the states
+//! - covers which are subsumed by another cover are discarded as they are built
+//! - states with identical outgoing edges are merged
+//!
+//! The resulting transition-based generalized Buchi automaton is then degeneralized
+//! with the standard counter construction, and states which can't lead to an
+//! accepting cycle are removed.
+//!
+//! The result uses the same naming conventions as `ltl3ba` never claims:
+//! the initial state's name ends with `_init`, and accepting states are prefixed with `accept_`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+
+use crate::logic::{LogicStatement, Ltl};
+
+use super::buchi::{BuchiState, StateName};
+
+/// A set of subformulas, by their index in [`Subformulas`]
+type Formulas = BTreeSet<usize>;
+
+/// A state of the degeneralized automaton, and the labelled edges out of each
+type Graph<T> = HashMap<(usize, usize), Vec<(T, (usize, usize))>>;
+
+/// The subformulas of an LTL formula in negation normal form.
+/// Working with indices rather than the formulas themselves keeps the
+/// (exponential) tableau expansion cheap.
+#[derive(Default)]
+struct Subformulas {
+    formulas: Vec<Ltl>,
+    kinds: Vec<Kind>,
+    index: HashMap<Ltl, usize>,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    /// Contains no temporal operators, so is treated as a literal
+    Propositional,
+    And(usize, usize),
+    Or(usize, usize),
+    Next(usize),
+    Until(usize, usize),
+    Release(usize, usize),
+}
+
+impl Subformulas {
+    fn intern(&mut self, ltl: &Ltl) -> usize {
+        if let Some(&i) = self.index.get(ltl) {
+            return i;
+        }
+        let kind = if ltl.is_propositional() {
+            Kind::Propositional
+        } else {
+            match ltl {
+                Ltl::And(p1, p2) => Kind::And(self.intern(p1), self.intern(p2)),
+                Ltl::Or(p1, p2) => Kind::Or(self.intern(p1), self.intern(p2)),
+                Ltl::Next(p) => Kind::Next(self.intern(p)),
+                Ltl::Until(p1, p2) => Kind::Until(self.intern(p1), self.intern(p2)),
+                Ltl::Release(p1, p2) => Kind::Release(self.intern(p1), self.intern(p2)),
+                other => unreachable!("formula is not in negation normal form: {other}"),
+            }
+        };
+        let i = self.formulas.len();
+        self.formulas.push(ltl.clone());
+        self.kinds.push(kind);
+        self.index.insert(ltl.clone(), i);
+        i
+    }
+}
+
+/// An edge of the transition-based generalized Buchi automaton,
+/// as produced by expanding the obligations of a state.
+struct Edge {
+    /// The edge is enabled if any of these conjunctions of propositional formulas hold
+    labels: Vec<Formulas>,
+    /// The formulas which must hold from the next step on, i.e. the target state
+    target: Formulas,
+    /// The until-formulas promised but not yet fulfilled by taking this edge
+    unfulfilled: Formulas,
+}
+
+/// One way of satisfying a formula: some propositional formulas which must
+/// hold now, the formulas which must hold from the next step on, and the
+/// until-formulas which are thereby left unfulfilled.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Cover {
+    label: Formulas,
+    next: Formulas,
+    unfulfilled: Formulas,
+}
+
+impl Cover {
+    fn dominates(&self, other: &Self) -> bool {
+        self.label.is_subset(&other.label)
+            && self.next.is_subset(&other.next)
+            && self.unfulfilled.is_subset(&other.unfulfilled)
+    }
+}
+
+/// Translate an LTL formula into the states of a Buchi automaton which accepts
+/// exactly the infinite words which satisfy the formula.
+pub(crate) fn translate(ltl: &Ltl) -> HashMap<StateName, Arc<BuchiState>> {
+    let mut subformulas = Subformulas::default();
+    let root = subformulas.intern(&ltl.clone().nnf());
+    let negation: Vec<Option<usize>> = subformulas
+        .formulas
+        .iter()
+        .map(|f| subformulas.index.get(&negate_literal(f)).copied())
+        .collect();
+    let covers = all_covers(&subformulas, &negation);
+
+    // Explore the generalized automaton, whose states are sets of obligations.
+    // State 0 is the initial state.
+    let mut obligations: Vec<Formulas> = vec![[root].into()];
+    let mut ids: HashMap<Formulas, usize> = HashMap::from([(obligations[0].clone(), 0)]);
+    let mut edges: Vec<Vec<(Vec<Formulas>, usize, Formulas)>> = vec![];
+    while edges.len() < obligations.len() {
+        let expanded = expand(&covers, &negation, &obligations[edges.len()])
+            .into_iter()
+            .map(|edge| {
+                let len = obligations.len();
+                let target = *ids.entry(edge.target.clone()).or_insert(len);
+                if target == len {
+                    obligations.push(edge.target);
+                }
+                (edge.labels, target, edge.unfulfilled)
+            })
+            .collect();
+        edges.push(expanded);
+    }
+
+    let representative = merge_equivalent(&edges);
+    for out in edges.iter_mut() {
+        for (_, target, _) in out.iter_mut() {
+            *target = representative[*target];
+        }
+    }
+
+    // Generalized acceptance: one set per until-formula, consisting of the edges
+    // which don't leave it unfulfilled.
+    let acceptance: Vec<usize> = edges
+        .iter()
+        .flatten()
+        .flat_map(|(_, _, unfulfilled)| unfulfilled.iter().copied())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    // Degeneralize: the states of the final automaton are (state, counter) pairs,
+    // where the counter tracks which acceptance set must be visited next.
+    // States whose counter has reached `k` are accepting, and count on from 0.
+    let k = acceptance.len();
+    let successors = |(s, i): (usize, usize)| -> Vec<(&Vec<Formulas>, (usize, usize))> {
+        edges[s]
+            .iter()
+            .map(|(labels, target, unfulfilled)| {
+                let mut j = if i == k { 0 } else { i };
+                while j < k && !unfulfilled.contains(&acceptance[j]) {
+                    j += 1;
+                }
+                (labels, (*target, j))
+            })
+            .collect()
+    };
+    let is_accepting = |(_, i): (usize, usize)| i == k;
+
+    let init = (0, 0);
+    let mut graph: Graph<&Vec<Formulas>> = HashMap::new();
+    let mut queue = VecDeque::from([init]);
+    while let Some(state) = queue.pop_front() {
+        if graph.contains_key(&state) {
+            continue;
+        }
+        let next = successors(state);
+        queue.extend(next.iter().map(|(_, s)| *s));
+        graph.insert(state, next);
+    }
+
+    let productive = productive_states(&graph, is_accepting);
+
+    let mut names: HashMap<(usize, usize), StateName> = HashMap::new();
+    let mut name = |state: (usize, usize)| -> StateName {
+        let len = names.len();
+        names
+            .entry(state)
+            .or_insert_with(|| match (state == init, is_accepting(state)) {
+                (true, true) => "accept_init".to_string(),
+                (true, false) => "T0_init".to_string(),
+                (false, true) => format!("accept_S{}", len + 1),
+                (false, false) => format!("T{}_S{}", state.1, len + 1),
+            })
+            .clone()
+    };
+    let label = |formulas: &Formulas| {
+        formulas
+            .iter()
+            .map(|&i| &subformulas.formulas[i])
+            .filter(|f| **f != Ltl::True)
+            .map(|f| f.to_logic_statement().unwrap())
+            .reduce(LogicStatement::and)
+            .unwrap_or(LogicStatement::True)
+    };
+
+    let mut states = HashMap::new();
+    let mut sorted: Vec<_> = graph.into_iter().collect();
+    sorted.sort_by_key(|(s, _)| *s);
+    for (state, next) in sorted {
+        if state != init && !productive.contains(&state) {
+            continue;
+        }
+        let predicates = next
+            .into_iter()
+            .filter(|(_, s)| productive.contains(s))
+            .flat_map(|(labels, s)| {
+                let target = name(s);
+                labels
+                    .iter()
+                    .map(|l| (label(l), target.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        states.insert(
+            name(state),
+            Arc::new(BuchiState::Conditional {
+                accepting: is_accepting(state),
+                predicates,
+            }),
+        );
+    }
+    states
+}
+
+/// Compute the covers of every subformula. Since subformulas are interned
+/// before the formulas containing them, a single pass suffices.
+fn all_covers(subformulas: &Subformulas, negation: &[Option<usize>]) -> Vec<Vec<Cover>> {
+    let single = |label: Formulas, next: Formulas, unfulfilled: Formulas| Cover {
+        label,
+        next,
+        unfulfilled,
+    };
+
+    let mut covers: Vec<Vec<Cover>> = vec![];
+    for (i, kind) in subformulas.kinds.iter().enumerate() {
+        let c = match *kind {
+            Kind::Propositional => match subformulas.formulas[i] {
+                Ltl::True => vec![single([].into(), [].into(), [].into())],
+                Ltl::False => vec![],
+                _ => vec![single([i].into(), [].into(), [].into())],
+            },
+            Kind::And(p1, p2) => product(&covers[p1], &covers[p2], negation),
+            Kind::Or(p1, p2) => union(&covers[p1], &covers[p2]),
+            Kind::Next(p) => vec![single([].into(), [p].into(), [].into())],
+            Kind::Until(p1, p2) => {
+                let pending = [single([].into(), [i].into(), [i].into())];
+                union(&covers[p2], &product(&covers[p1], &pending, negation))
+            }
+            Kind::Release(p1, p2) => {
+                let pending = [single([].into(), [i].into(), [].into())];
+                union(
+                    &product(&covers[p2], &pending, negation),
+                    &product(&covers[p1], &covers[p2], negation),
+                )
+            }
+        };
+        covers.push(c);
+    }
+    covers
+}
+
+/// All the ways of satisfying both of two formulas, given the ways of
+/// satisfying each.
+fn product(c1: &[Cover], c2: &[Cover], negation: &[Option<usize>]) -> Vec<Cover> {
+    let mut out = vec![];
+    for a in c1 {
+        for b in c2 {
+            let label: Formulas = a.label.union(&b.label).copied().collect();
+            if label
+                .iter()
+                .any(|&l| negation[l].is_some_and(|n| label.contains(&n)))
+            {
+                // contradiction
+                continue;
+            }
+            out.push(Cover {
+                label,
+                next: a.next.union(&b.next).copied().collect(),
+                unfulfilled: a.unfulfilled.union(&b.unfulfilled).copied().collect(),
+            });
+        }
+    }
+    prune(out)
+}
+
+/// All the ways of satisfying either of two formulas, given the ways of
+/// satisfying each.
+fn union(c1: &[Cover], c2: &[Cover]) -> Vec<Cover> {
+    prune(c1.iter().chain(c2).cloned().collect())
+}
+
+/// Remove redundant covers.
+///
+/// A cover is redundant if another requires no more of the current step,
+/// leaves a subset of obligations for the next (hence a superset of
+/// the language), and leaves no more untils unfulfilled.
+fn prune(mut covers: Vec<Cover>) -> Vec<Cover> {
+    covers.sort();
+    covers.dedup();
+    let keep: Vec<bool> = covers
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            !covers
+                .iter()
+                .enumerate()
+                .any(|(j, d)| i != j && d.dominates(c))
+        })
+        .collect();
+    covers
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(c, keep)| keep.then_some(c))
+        .collect()
+}
+
+/// Expand a set of obligations into the edges leaving the state which they represent.
+/// Edges with the same target and acceptance are merged.
+fn expand(covers: &[Vec<Cover>], negation: &[Option<usize>], obligations: &Formulas) -> Vec<Edge> {
+    let all = obligations.iter().fold(
+        vec![Cover {
+            label: [].into(),
+            next: [].into(),
+            unfulfilled: [].into(),
+        }],
+        |acc, &f| product(&acc, &covers[f], negation),
+    );
+    let mut edges: BTreeMap<(Formulas, Formulas), Vec<Formulas>> = BTreeMap::new();
+    for c in all {
+        edges
+            .entry((c.next, c.unfulfilled))
+            .or_default()
+            .push(c.label);
+    }
+    edges
+        .into_iter()
+        .map(|((target, unfulfilled), labels)| Edge {
+            labels,
+            target,
+            unfulfilled,
+        })
+        .collect()
+}
+
+/// Merge states which have exactly the same outgoing edges, up to merging,
+/// returning the representative of each state's equivalence class.
+fn merge_equivalent(edges: &[Vec<(Vec<Formulas>, usize, Formulas)>]) -> Vec<usize> {
+    let mut class = vec![0; edges.len()];
+    loop {
+        let mut signatures: HashMap<_, usize> = HashMap::new();
+        let mut representatives = vec![];
+        let refined: Vec<usize> = edges
+            .iter()
+            .enumerate()
+            .map(|(s, out)| {
+                let signature: BTreeSet<_> = out
+                    .iter()
+                    .map(|(labels, target, unfulfilled)| (labels, class[*target], unfulfilled))
+                    .collect();
+                let len = signatures.len();
+                let c = *signatures.entry((class[s], signature)).or_insert(len);
+                if c == len {
+                    representatives.push(s);
+                }
+                c
+            })
+            .collect();
+        let stable = representatives.len() == class.iter().collect::<HashSet<_>>().len();
+        class = refined;
+        if stable {
+            return class.into_iter().map(|c| representatives[c]).collect();
+        }
+    }
+}
+
+fn negate_literal(ltl: &Ltl) -> Ltl {
+    match ltl {
+        Ltl::Not(p) => (**p).clone(),
+        Ltl::True => Ltl::False,
+        Ltl::False => Ltl::True,
+        other => other.clone().not(),
+    }
+}
+
+/// The set of states from which an accepting cycle is reachable.
+/// Any other states can never lead to acceptance, and can be discarded.
+fn productive_states<T>(
+    graph: &Graph<T>,
+    is_accepting: impl Fn((usize, usize)) -> bool,
+) -> HashSet<(usize, usize)> {
+    let successors = |s: &(usize, usize)| graph[s].iter().map(|(_, next)| *next);
+    let reachable_from = |start: (usize, usize)| {
+        let mut seen = HashSet::new();
+        let mut queue: VecDeque<_> = successors(&start).collect();
+        while let Some(s) = queue.pop_front() {
+            if seen.insert(s) {
+                queue.extend(successors(&s));
+            }
+        }
+        seen
+    };
+
+    let mut productive: HashSet<(usize, usize)> = graph
+        .keys()
+        .copied()
+        .filter(|&s| is_accepting(s) && reachable_from(s).contains(&s))
+        .collect();
+
+    loop {
+        let more: Vec<_> = graph
+            .keys()
+            .copied()
+            .filter(|s| !productive.contains(s) && successors(s).any(|n| productive.contains(&n)))
+            .collect();
+        if more.is_empty() {
+            break;
+        }
+        productive.extend(more);
+    }
+    productive
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logic::EvaluatePropositions;
+
+    use super::*;
+
+    struct Letter<'a>(&'a [&'a str]);
+
+    impl EvaluatePropositions<String> for Letter<'_> {
+        fn evaluate(&self, p: &String) -> bool {
+            self.0.contains(&p.as_str())
+        }
+    }
+
+    /// Check whether the automaton accepts the infinite word `prefix (cycle)^ω`
+    fn accepts(ltl: &str, prefix: &[&[&str]], cycle: &[&[&str]]) -> bool {
+        let states = translate(&Ltl::parse(ltl).unwrap());
+        let word: Vec<_> = prefix.iter().chain(cycle.iter()).collect();
+        let wrap = |pos: usize| {
+            if pos + 1 == word.len() {
+                prefix.len()
+            } else {
+                pos + 1
+            }
+        };
+        let step = |(name, pos): &(StateName, usize)| -> Vec<(StateName, usize)> {
+            match &*states[name] {
+                BuchiState::Conditional { predicates, .. } => predicates
+                    .iter()
+                    .filter(|(p, _)| p.eval(&Letter(word[*pos])))
+                    .map(|(_, next)| (next.clone(), wrap(*pos)))
+                    .collect(),
+                BuchiState::Skip => vec![(name.clone(), wrap(*pos))],
+            }
+        };
+        let reachable = |from: Vec<(StateName, usize)>| {
+            let mut seen = HashSet::new();
+            let mut queue = VecDeque::from(from);
+            while let Some(s) = queue.pop_front() {
+                if seen.insert(s.clone()) {
+                    queue.extend(step(&s));
+                }
+            }
+            seen
+        };
+        let init = states.keys().find(|n| n.ends_with("_init")).unwrap();
+        reachable(step(&(init.clone(), 0)))
+            .into_iter()
+            .filter(|(name, pos)| name.starts_with("accept_") && *pos >= prefix.len())
+            .any(|s| reachable(step(&s)).contains(&s))
+    }
+
+    #[test]
+    fn test_translate_basic() {
+        assert!(accepts("G p", &[], &[&["p"]]));
+        assert!(!accepts("G p", &[&["p"], &[]], &[&["p"]]));
+
+        assert!(accepts("F p", &[&[]], &[&["p"]]));
+        assert!(!accepts("F p", &[], &[&[]]));
+
+        assert!(accepts("X p", &[&[], &["p"]], &[&[]]));
+        assert!(!accepts("X p", &[&["p"], &[]], &[&[]]));
+
+        assert!(accepts("p U q", &[&["p"], &["p"], &["q"]], &[&[]]));
+        assert!(!accepts("p U q", &[&["p"], &[], &["q"]], &[&[]]));
+        assert!(!accepts("p U q", &[], &[&["p"]]));
+
+        assert!(accepts("p R q", &[], &[&["q"]]));
+        assert!(accepts("p R q", &[&["q"], &["p", "q"]], &[&[]]));
+        assert!(!accepts("p R q", &[&["q"], &["p"]], &[&[]]));
+
+        assert!(accepts("p W q", &[], &[&["p"]]));
+        assert!(!accepts("p W q", &[&["p"]], &[&[]]));
+    }
+
+    #[test]
+    fn test_translate_fairness_shapes() {
+        assert!(accepts("G F p", &[], &[&["p"], &[]]));
+        assert!(!accepts("G F p", &[&["p"]], &[&[]]));
+
+        assert!(accepts("F G p", &[&[]], &[&["p"]]));
+        assert!(!accepts("F G p", &[], &[&["p"], &[]]));
+
+        assert!(accepts("G (p -> F q)", &[&["p"]], &[&[], &["q"]]));
+        assert!(!accepts("G (p -> F q)", &[], &[&["p"], &[]]));
+
+        assert!(accepts("G F p && G F q", &[], &[&["p"], &["q"]]));
+        assert!(!accepts("G F p && G F q", &[], &[&["p"], &["p"]]));
+    }
+
+    #[test]
+    fn test_translate_unsatisfiable() {
+        assert!(!accepts("p && !p", &[&["p"]], &[&["p"]]));
+        assert!(!accepts("G p && F !p", &[], &[&["p"]]));
+    }
+}
@@ -1,15 +1,26 @@
 use crate::logic::{conjoin, EvaluatePropositions};
+use crate::traversal::Compaction;
 
 use super::*;
 
 const MODULO: usize = 16;
 
+#[allow(unused)]
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 struct TestMachine1;
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 struct TestMachine2;
 
+/// Two bits, `a` and `b`. Action `true` flips `a`, but only while `b` is unset.
+/// Action `false` flips `b`.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct TestMachine3;
+
+/// Counts up to 3, and then can't go any further.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct TestMachine4;
+
 const LOOP: u8 = 4;
 
 impl Machine for TestMachine1 {
@@ -53,6 +64,67 @@ impl Machine for TestMachine2 {
     }
 }
 
+impl Machine for TestMachine3 {
+    type State = (bool, bool);
+    type Action = bool;
+    type Error = anyhow::Error;
+    type Fx = ();
+
+    fn transition(&self, (a, b): Self::State, flip_a: Self::Action) -> TransitionResult<Self> {
+        if flip_a {
+            if b {
+                anyhow::bail!("a is locked while b is set");
+            }
+            Ok(((!a, b), ()))
+        } else {
+            Ok(((a, !b), ()))
+        }
+    }
+
+    fn is_terminal(&self, _: &Self::State) -> bool {
+        false
+    }
+}
+
+impl Machine for TestMachine4 {
+    type State = u8;
+    type Action = ();
+    type Error = anyhow::Error;
+    type Fx = ();
+
+    fn transition(&self, state: Self::State, (): Self::Action) -> TransitionResult<Self> {
+        if state == 3 {
+            anyhow::bail!("can't count past 3");
+        }
+        Ok((state + 1, ()))
+    }
+
+    fn is_terminal(&self, _: &Self::State) -> bool {
+        false
+    }
+}
+
+impl EvaluatePropositions<String> for Transition<TestMachine4> {
+    fn evaluate(&self, p: &String) -> bool {
+        let Transition(s, _, _) = *self;
+        match p.as_str() {
+            "top" => s == 3,
+            p => unreachable!("can't eval unknown prop '{p}'"),
+        }
+    }
+}
+
+impl EvaluatePropositions<String> for Transition<TestMachine3> {
+    fn evaluate(&self, p: &String) -> bool {
+        let Transition((a, b), _, _) = *self;
+        match p.as_str() {
+            "a" => a,
+            "b" => b,
+            p => unreachable!("can't eval unknown prop '{p}'"),
+        }
+    }
+}
+
 impl EvaluatePropositions<String> for Transition<TestMachine2> {
     fn evaluate(&self, p: &String) -> bool {
         let Transition(s, _, ss) = *self;
@@ -105,7 +177,8 @@ fn model_checker_test() {
     // true positives:
     let positives = conjoin([
         "G ( (is2 && X is8) -> G F is11)",
-        "G ( is1 -> (G F is5 || G F is3 || G F is8) )",
+        // (3 is only ever visited once, on the way into the 12..15 loop)
+        "G ( is1 -> (G F is5 || G F is15 || G F is8) )",
         "G ( increasing || X loopmin )",
     ]);
 
@@ -208,6 +281,199 @@ fn model_checker_test() {
     // }
 }
 
+#[test]
+fn model_checker_liveness_lasso() {
+    // From 1, the machine can end up cycling through 8..11 forever, never reaching 5
+    let err = TestMachine2
+        .traverse([1])
+        .specced((), "G F is5")
+        .unwrap()
+        .model_check()
+        .unwrap_err();
+
+    let ModelCheckerError::Liveness { lasso } = err else {
+        panic!("expected a liveness error, got {err:?}");
+    };
+
+    assert_eq!(lasso.initial, 1);
+    assert!(!lasso.cycle.is_empty());
+
+    // the lasso must be a real path through the machine
+    let mut state = lasso.initial;
+    for (action, next) in lasso.prefix.iter().chain(lasso.cycle.iter()) {
+        state = TestMachine2.transition_(state, *action).unwrap();
+        assert_eq!(state, *next);
+    }
+    let start = lasso
+        .prefix
+        .last()
+        .map(|(_, s)| *s)
+        .unwrap_or(lasso.initial);
+    assert_eq!(state, start, "cycle must return to where it started");
+    assert!(lasso.cycle.iter().all(|(_, s)| *s != 5));
+}
+
+#[test]
+fn model_checker_deterministic() {
+    let check = || {
+        TestMachine2
+            .traverse([1])
+            .deterministic(true)
+            .specced((), "G F is5")
+            .unwrap()
+            .model_check()
+            .unwrap_err()
+    };
+    let first = format!("{:?}", check());
+    for _ in 0..10 {
+        assert_eq!(format!("{:?}", check()), first);
+    }
+}
+
+#[test]
+fn model_checker_liveness_satisfied() {
+    // Every cycle stays within a group of 4, and every group contains a multiple of 4
+    TestMachine2
+        .traverse([1])
+        .specced((), "G F loopmin")
+        .unwrap()
+        .model_check()
+        .unwrap();
+}
+
+#[test]
+fn model_checker_hash_compaction() {
+    let report = TestMachine2
+        .traverse([1])
+        .compaction(Compaction::HashCompaction)
+        .unwrap()
+        .specced((), "G F loopmin")
+        .unwrap()
+        .model_check()
+        .unwrap();
+    assert!(report.omission_probability.unwrap() < 1e-12);
+
+    // Counterexamples still come with the path to them
+    let err = TestMachine2
+        .traverse([1])
+        .compaction(Compaction::HashCompaction)
+        .unwrap()
+        .specced((), "G !is9")
+        .unwrap()
+        .model_check()
+        .unwrap_err();
+    let ModelCheckerError::Safety {
+        path,
+        states: (cur, next),
+    } = err
+    else {
+        panic!("expected a safety error, got {err:?}");
+    };
+    assert_eq!(cur, 9);
+    let state = path
+        .into_iter()
+        .fold(1, |s, action| TestMachine2.transition_(s, action).unwrap());
+    assert_eq!(state, next);
+}
+
+#[cfg(feature = "recording")]
+#[test]
+fn model_checker_resume() {
+    let path = std::env::temp_dir().join(format!("polestar-mc-test-{}", std::process::id()));
+    let traversal = |ltl| TestMachine2.traverse([1]).specced((), ltl).unwrap();
+
+    let report = traversal("G F loopmin")
+        .checkpoint(&path, std::time::Duration::ZERO)
+        .unwrap()
+        .model_check()
+        .unwrap();
+    // The last checkpoint holds the whole graph, with nothing left to visit
+    let resumed = traversal("G F loopmin")
+        .resume_from(&path)
+        .unwrap()
+        .model_check()
+        .unwrap();
+    assert_eq!(resumed.num_visited, report.num_visited);
+    assert_eq!(resumed.total_steps, report.total_steps);
+
+    let expected = traversal("G F is5").model_check().unwrap_err();
+    traversal("G F is5")
+        .checkpoint(&path, std::time::Duration::ZERO)
+        .unwrap()
+        .model_check()
+        .unwrap_err();
+    let resumed = traversal("G F is5")
+        .resume_from(&path)
+        .unwrap()
+        .model_check()
+        .unwrap_err();
+    assert_eq!(format!("{resumed:?}"), format!("{expected:?}"));
+    std::fs::remove_file(path).unwrap();
+
+    // The states are only spilled once they are those of the model checker
+    let early = TestMachine2
+        .traverse([1])
+        .spill_to_disk(std::env::temp_dir(), 10)
+        .unwrap();
+    assert!(early.specced((), "G F loopmin").is_err());
+}
+
+#[test]
+fn model_checker_weak_fairness() {
+    let traversal = || TestMachine3.traverse([(false, false)]).specced((), "G F b");
+
+    // b can be ignored forever by flipping a back and forth
+    assert!(matches!(
+        traversal().unwrap().model_check(),
+        Err(ModelCheckerError::Liveness { .. })
+    ));
+
+    // but flipping b is always enabled, so under weak fairness it must happen
+    traversal()
+        .unwrap()
+        .weak_fairness(|flip_a| !flip_a)
+        .model_check()
+        .unwrap();
+}
+
+#[test]
+fn model_checker_strong_fairness() {
+    let traversal = || TestMachine3.traverse([(false, false)]).specced((), "G F a");
+
+    // flipping b back and forth keeps disabling a, so weak fairness doesn't help
+    let err = traversal()
+        .unwrap()
+        .weak_fairness(|flip_a| *flip_a)
+        .model_check()
+        .unwrap_err();
+    let ModelCheckerError::Liveness { lasso } = err else {
+        panic!("expected a liveness error, got {err:?}");
+    };
+    assert!(lasso.cycle.iter().all(|(flip_a, _)| !flip_a));
+
+    // but a is enabled infinitely often, so under strong fairness it must be flipped
+    traversal()
+        .unwrap()
+        .strong_fairness(|flip_a| *flip_a)
+        .model_check()
+        .unwrap();
+}
+
+#[test]
+fn model_checker_deadlock() {
+    let traversal = || TestMachine4.traverse([0]).specced((), "F top").unwrap();
+
+    // Getting stuck at the top doesn't break the spec
+    traversal().model_check().unwrap();
+
+    let err = traversal().check_deadlock(true).model_check().unwrap_err();
+    let ModelCheckerError::Deadlock { initial, path } = err else {
+        panic!("expected a deadlock, got {err:?}");
+    };
+    assert_eq!(initial, 0);
+    assert_eq!(path, [((), 1), ((), 2), ((), 3)]);
+}
+
 #[test]
 #[ignore = "diagram"]
 fn model_checker_diagram() {
@@ -6,36 +6,73 @@
 //! - Terminal state discovery
 
 use colored::Colorize;
+use crossbeam::deque::{Injector, Steal, Stealer, Worker};
+use crossbeam::utils::Backoff;
 use exhaustive::Exhaustive;
 use human_repr::HumanCount;
 use itertools::Itertools;
 use parking_lot::Mutex;
-use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::graph::{DiGraph, EdgeIndex, NodeIndex};
+use petgraph::visit::EdgeRef;
+use proptest::prelude::Arbitrary;
+use proptest::strategy::Strategy as ValueStrategy;
+use proptest::test_runner::TestRunner;
 
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::sync::atomic::Ordering::SeqCst;
 use std::{
-    collections::{HashMap, HashSet},
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Debug,
     hash::Hash,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, AtomicUsize},
-        Arc,
+        Arc, OnceLock,
     },
 };
 
+use crate::generate::Generator;
+use crate::id::{Id, Relabel, Symmetry};
 use crate::logic::{EvaluatePropositions, PropositionMapping, Transition};
 use crate::machine::Cog;
-use crate::model_checker::{ModelCheckerError, ModelCheckerState, ModelCheckerTransitionError};
+use crate::model_checker::liveness::{find_lasso, LivenessEdge};
+use crate::model_checker::{
+    Fairness, ModelCheckerError, ModelCheckerState, ModelCheckerTransitionError,
+};
 use crate::prelude::ModelChecker;
 use crate::{util::first, Machine};
 
-/// Represents a breadth-first traversal of a [`Machine`]'s state graph.
+mod budget;
+mod checkpoint;
+mod coverage;
+mod progress;
+mod refinement;
+mod search;
+mod sharded;
+mod simulate;
+mod storage;
+mod swarm;
+use budget::Budget;
+pub use budget::{CancelHandle, Incomplete};
+pub use checkpoint::Checkpointed;
+use checkpoint::{Checkpoint, Checkpointing};
+pub use coverage::{ActionCoverage, Coverage};
+pub use progress::{DepthStats, Progress};
+pub use refinement::{RefinementFailure, RefinementViolation};
+pub use search::{Cost, CostedPath};
+use sharded::Sharded;
+pub use simulate::{FailedWalk, Simulation, WalkFailure};
+use storage::{Codec, Codecs, Frontier, Spill, SpillDir, Visited};
+pub use swarm::SwarmReport;
+
+/// Represents a traversal of a [`Machine`]'s state graph, breadth-first by default
+/// (see [`Traversal::strategy`]).
 /// This is the starting point for many useful operations, like model checking, graphing,
 /// or searching for solutions to combinatorial problems.
 ///
 /// The type has a variety of builder-like methods to configure how the traversal will
 /// be performed, as well as a handful of functions which kick off the traversal.
-#[derive(Clone)]
 #[allow(clippy::type_complexity)]
 pub struct Traversal<M: Machine, S = <M as Machine>::State, A = <M as Machine>::Action> {
     /// The machine to traverse.
@@ -44,35 +81,139 @@ pub struct Traversal<M: Machine, S = <M as Machine>::State, A = <M as Machine>::
     /// The initial states to start the traversal from.
     pub initial: im::Vector<M::State>,
 
+    options: TraversalOptions,
+    codecs: Option<TraversalCodecs<M, S, A>>,
+    /// The checkpoint to continue from, as read by [`Traversal::resume_from`]
+    resumed: Option<Arc<Checkpoint<S, Queued<M>>>>,
+
+    visitor: Arc<dyn Fn(&M::State, VisitType) -> Result<(), M::Error> + Send + Sync>,
+    is_fatal_error: Arc<dyn Fn(&M::Error) -> bool + Send + Sync>,
+    /// The actions to try in states for which the machine lists none: every possible
+    /// action, if the action type is [`Exhaustive`]
+    all_actions: fn() -> Vec<M::Action>,
+    /// Generates an arbitrary action to try, for simulations
+    arbitrary_action: Option<Arc<dyn Fn(&mut TestRunner) -> M::Action + Send + Sync>>,
+    independence: Option<Arc<dyn Fn(&M::Action, &M::Action, &M::State) -> bool + Send + Sync>>,
+    invariants: Vec<(String, Arc<dyn Fn(&M::State) -> bool + Send + Sync>)>,
+    /// The kind of each action, if coverage is being recorded
+    coverage: Option<Arc<dyn Fn(&M::Action) -> String + Send + Sync>>,
+    /// The states to search for, and how many to find before stopping
+    target: Option<(Arc<dyn Fn(&M::State) -> bool + Send + Sync>, usize)>,
+    /// Relabels states and actions, for symmetry reduction
+    symmetry: Option<Canonicalizer<M::State, M::Action>>,
+    map_state: Arc<dyn Fn(M::State) -> Option<S> + Send + Sync>,
+    /// Maps the action of each edge, given the state which it leads to
+    map_action: Arc<dyn Fn(&M::State, M::Action) -> Option<A> + Send + Sync>,
+}
+
+/// The options of a [`Traversal`] which don't depend on the types of its machine,
+/// states or actions, and so carry over unchanged when those types change
+#[derive(Clone, Default)]
+#[allow(clippy::type_complexity)]
+struct TraversalOptions {
+    strategy: Strategy,
+    compaction: Compaction,
+    deterministic: bool,
+    threads: Option<usize>,
     max_depth: Option<usize>,
     trace_every: Option<usize>,
     trace_errors: bool,
     ignore_loopbacks: bool,
+    check_deadlock: bool,
+    budget: Budget,
+    /// Seeds a shuffle of the actions tried in each state, for members of a swarm
+    shuffle: Option<u64>,
+    spill: Option<Spill>,
+    checkpoint: Option<Checkpointing>,
+    /// How many steps to take between progress snapshots, and what to do with each
+    progress: Option<(usize, Arc<dyn Fn(Progress) + Send + Sync>)>,
+}
 
-    visitor: Arc<dyn Fn(&M::State, VisitType) -> Result<(), M::Error> + Send + Sync>,
-    is_fatal_error: Arc<dyn Fn(&M::Error) -> bool + Send + Sync>,
-    map_state: Arc<dyn Fn(M::State) -> Option<S> + Send + Sync>,
-    map_action: Arc<dyn Fn(M::Action) -> Option<A> + Send + Sync>,
+impl TraversalOptions {
+    /// Fail unless checkpoints can be written and resumed from with these options,
+    /// which needs every level of a breadth-first traversal, and every state visited,
+    /// to be held in memory
+    fn supports_checkpoints(&self) -> std::io::Result<()> {
+        if self.strategy == Strategy::Bfs
+            && self.compaction == Compaction::Exact
+            && self.spill.is_none()
+        {
+            Ok(())
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "checkpoints are only supported by Strategy::Bfs, \
+                 with Compaction::Exact and without spilling to disk",
+            ))
+        }
+    }
+}
+
+// Implemented by hand, since deriving would require the error type to be Clone
+impl<M: Machine + Clone, S, A> Clone for Traversal<M, S, A> {
+    fn clone(&self) -> Self {
+        Self {
+            machine: self.machine.clone(),
+            initial: self.initial.clone(),
+            options: self.options.clone(),
+            codecs: self.codecs.clone(),
+            resumed: self.resumed.clone(),
+            visitor: self.visitor.clone(),
+            is_fatal_error: self.is_fatal_error.clone(),
+            all_actions: self.all_actions,
+            arbitrary_action: self.arbitrary_action.clone(),
+            independence: self.independence.clone(),
+            invariants: self.invariants.clone(),
+            coverage: self.coverage.clone(),
+            target: self.target.clone(),
+            symmetry: self.symmetry.clone(),
+            map_state: self.map_state.clone(),
+            map_action: self.map_action.clone(),
+        }
+    }
 }
 
 impl<M: Machine> Traversal<M>
 where
     M::State: Clone + Debug,
-    M::Action: Clone + Debug + Exhaustive,
+    M::Action: Clone + Debug,
 {
     /// Initialize the Traversal.
-    pub fn new(machine: M, initial: impl IntoIterator<Item = M::State>) -> Self {
+    pub fn new(machine: M, initial: impl IntoIterator<Item = M::State>) -> Self
+    where
+        M::Action: Exhaustive,
+    {
+        Self::with_enabled_actions(machine, initial, || {
+            M::Action::iter_exhaustive(None).collect()
+        })
+    }
+
+    /// Initialize the Traversal of a machine whose actions need not be [`Exhaustive`],
+    /// since it lists the actions to try in each state (see [`Machine::enabled_actions`]).
+    /// In any state for which the machine lists none, the actions returned by
+    /// `all_actions` are tried instead, which may be none at all, e.g. with `Vec::new`.
+    pub fn with_enabled_actions(
+        machine: M,
+        initial: impl IntoIterator<Item = M::State>,
+        all_actions: fn() -> Vec<M::Action>,
+    ) -> Self {
         Self {
             machine,
             initial: initial.into_iter().collect(),
-            max_depth: None,
-            trace_every: None,
-            trace_errors: false,
-            ignore_loopbacks: false,
+            options: TraversalOptions::default(),
+            codecs: None,
+            resumed: None,
             visitor: Arc::new(|_, _| Ok(())),
             is_fatal_error: Arc::new(|_| false),
-            map_state: Arc::new(|s| Some(s)),
-            map_action: Arc::new(|a| Some(a)),
+            all_actions,
+            arbitrary_action: None,
+            independence: None,
+            invariants: vec![],
+            coverage: None,
+            target: None,
+            symmetry: None,
+            map_state: Arc::new(Some),
+            map_action: Arc::new(|_, a| Some(a)),
         }
     }
 }
@@ -80,27 +221,75 @@ where
 impl<M, S, A> Traversal<M, S, A>
 where
     M: Machine,
-    M::Action: Exhaustive,
     S: Cog + Hash + Eq + 'static,
     A: Cog + Hash + Eq + 'static,
 {
+    /// Choose the order in which states are explored.
+    /// The default is [`Strategy::Bfs`].
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.options.strategy = strategy;
+        self
+    }
+
+    /// Choose how visited states are remembered.
+    /// The default is [`Compaction::Exact`]; the other options use far less memory,
+    /// at the cost of a small chance of missing part of the state space,
+    /// which is estimated in [`TraversalReport::omission_probability`].
+    ///
+    /// This fails if the bit array of [`Compaction::Bitstate`] is too large to allocate.
+    pub fn compaction(mut self, compaction: Compaction) -> anyhow::Result<Self> {
+        if let Compaction::Bitstate { log2_bits, .. } = compaction {
+            // The array is only allocated when the traversal starts, so check now
+            // that the allocator could provide it
+            let allocatable = 1usize.checked_shl(log2_bits).is_some_and(|bits| {
+                Vec::<u64>::new()
+                    .try_reserve_exact(bits.div_ceil(64))
+                    .is_ok()
+            });
+            if !allocatable {
+                anyhow::bail!("a bit array of 2^{log2_bits} bits is too large to allocate");
+            }
+        }
+        self.options.compaction = compaction;
+        Ok(self)
+    }
+
+    /// Run the traversal on a single thread, so that every run explores states in the
+    /// same order: actions are tried in the order given by [`Machine::enabled_actions`]
+    /// or else [`Exhaustive`], graph nodes are
+    /// numbered identically, and the same counterexample is found first.
+    /// Useful for reproducing failures and for diffing graph outputs.
+    ///
+    /// This overrides [`Traversal::threads`].
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.options.deterministic = deterministic;
+        self
+    }
+
+    /// Set the number of threads used by [`Strategy::Bfs`] and [`Strategy::Parallel`].
+    /// The default is one per CPU core. Other strategies always use a single thread.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.options.threads = Some(threads.max(1));
+        self
+    }
+
     /// Set the maximum depth of the graph to be traversed.
     /// This can be used to perform bounded model checking.
     pub fn max_depth(mut self, max_depth: usize) -> Self {
-        self.max_depth = Some(max_depth);
+        self.options.max_depth = Some(max_depth);
         self
     }
 
     /// Periodically print a log message after this many steps.
     /// Useful to see progress in long-running traversals.
     pub fn trace_every(mut self, trace_every: usize) -> Self {
-        self.trace_every = Some(trace_every);
+        self.options.trace_every = Some(trace_every);
         self
     }
 
     /// Whether to print a log message for each error encountered.
     pub fn trace_errors(mut self, trace_errors: bool) -> Self {
-        self.trace_errors = trace_errors;
+        self.options.trace_errors = trace_errors;
         self
     }
 
@@ -108,7 +297,164 @@ where
     /// This is primarily intended to reduce clutter on state diagrams.
     // TODO: does this have negative implications on model checking?
     pub fn ignore_loopbacks(mut self, ignore_loopbacks: bool) -> Self {
-        self.ignore_loopbacks = ignore_loopbacks;
+        self.options.ignore_loopbacks = ignore_loopbacks;
+        self
+    }
+
+    /// Stop once `max_states` distinct states have been visited, leaving the traversal
+    /// incomplete (see [`TraversalReport::incomplete`]).
+    pub fn max_states(mut self, max_states: usize) -> Self {
+        self.options.budget.max_states = Some(max_states);
+        self
+    }
+
+    /// Stop once the traversal has been running for `time_limit`, since it was started
+    /// or resumed, leaving it incomplete (see [`TraversalReport::incomplete`]).
+    pub fn time_limit(mut self, time_limit: std::time::Duration) -> Self {
+        self.options.budget.time_limit = Some(time_limit);
+        self
+    }
+
+    /// Stop once the estimated memory used goes over `bytes`, leaving the traversal
+    /// incomplete (see [`TraversalReport::incomplete`]). The estimate is rough, and
+    /// only checked every so often (see [`Progress::memory_estimate`]).
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.options.budget.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Stop when the handle is cancelled, e.g. from another thread,
+    /// leaving the traversal incomplete (see [`TraversalReport::incomplete`]).
+    pub fn cancel_handle(mut self, handle: CancelHandle) -> Self {
+        self.options.budget.cancel = Some(handle);
+        self
+    }
+
+    /// Treat a state which is not terminal, but in which every action returns an error,
+    /// as a failure: a deadlock. [`Traversal::check_invariants`] reports the closest one
+    /// as a violation named "deadlock", and [`Traversal::model_check`] fails with
+    /// [`ModelCheckerError::Deadlock`]. Deadlocks are always counted in the report.
+    ///
+    /// Errors which are skipped count as not being able to take the action,
+    /// so this is only meaningful if the machine doesn't error on legitimate states.
+    pub fn check_deadlock(mut self, check_deadlock: bool) -> Self {
+        self.options.check_deadlock = check_deadlock;
+        self
+    }
+
+    /// Keep at most about `max_in_memory` visited states, and as many queued states,
+    /// in memory, writing the rest to files in a fresh directory within `dir`,
+    /// which is removed once the traversal is done.
+    /// This allows state spaces which don't fit in memory to be explored to completion,
+    /// though much more slowly.
+    ///
+    /// States are encoded as JSON. Spilled visited states are found again by their hash,
+    /// using a small index and Bloom filter kept in memory for each file, so memory use
+    /// still grows with the number of states, but by only a few bytes each.
+    ///
+    /// Only [`Strategy::Bfs`] spills its frontier: the other strategies keep their queues
+    /// in memory. Only [`Compaction::Exact`] spills visited states, since the other
+    /// options keep them compact enough already. A graph built by [`Traversal::diagram`]
+    /// or by model checking is also kept in memory.
+    /// [`Traversal::specced`] changes the type of the states, so call this after it.
+    ///
+    /// `dir` is created if need be, and this fails if it can't be, or if no directory
+    /// can be created within it.
+    #[cfg(feature = "recording")]
+    pub fn spill_to_disk(
+        mut self,
+        dir: impl Into<PathBuf>,
+        max_in_memory: usize,
+    ) -> std::io::Result<Self>
+    where
+        S: serde::Serialize + serde::de::DeserializeOwned,
+        M::State: serde::Serialize + serde::de::DeserializeOwned + 'static,
+        M::Action: serde::Serialize + serde::de::DeserializeOwned + 'static,
+        A: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        // The directory the traversal will spill to is created and removed again,
+        // so that a directory which can't be written to is found now
+        drop(SpillDir::create(&dir)?);
+        self.options.spill = Some(Spill { dir, max_in_memory });
+        Ok(self.serialized())
+    }
+
+    /// Periodically write a checkpoint to the given file, from which the traversal
+    /// can be continued with [`Traversal::resume_from`] if it is interrupted.
+    /// A checkpoint holds the visited states, the graph, the queued states and the
+    /// counters of the report, encoded as JSON, and replaces the previous checkpoint
+    /// only once it has been written in full.
+    ///
+    /// Checkpoints are written between the levels of a [`Strategy::Bfs`] traversal,
+    /// once at least `every` has passed since the last one, so a very wide level may
+    /// delay them. Only [`Strategy::Bfs`] with [`Compaction::Exact`] and without
+    /// [`Traversal::spill_to_disk`] supports checkpoints, so this fails with
+    /// [`std::io::ErrorKind::InvalidInput`] if the traversal is configured otherwise.
+    /// Since those options can't be changed afterwards, this returns a [`Checkpointed`]
+    /// traversal, which can only be run, so call this once the traversal is configured,
+    /// and after [`Traversal::specced`], which changes the type of the states.
+    #[cfg(feature = "recording")]
+    pub fn checkpoint(
+        self,
+        path: impl Into<PathBuf>,
+        every: std::time::Duration,
+    ) -> std::io::Result<Checkpointed<M, S, A>>
+    where
+        S: serde::Serialize + serde::de::DeserializeOwned,
+        M::State: serde::Serialize + serde::de::DeserializeOwned + 'static,
+        M::Action: serde::Serialize + serde::de::DeserializeOwned + 'static,
+        A: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.options.supports_checkpoints()?;
+        Ok(Checkpointed(self.serialized()).checkpoint(path, every))
+    }
+
+    /// Continue the traversal from a checkpoint written by [`Traversal::checkpoint`],
+    /// rather than from the initial states. The traversal must be configured just as
+    /// the one which wrote the checkpoint, and the report covers both of them.
+    ///
+    /// The checkpoint is read straight away, failing if it can't be read or decoded,
+    /// or if the traversal is configured in a way which doesn't support checkpoints.
+    /// Like [`Traversal::checkpoint`], this returns a [`Checkpointed`] traversal,
+    /// which can only be run.
+    ///
+    /// # Panics
+    ///
+    /// The traversal panics if the checkpoint was written by a traversal run for
+    /// something else, e.g. by [`Traversal::diagram`] and resumed by
+    /// [`Traversal::check_invariants`], since the edges of their graphs differ.
+    #[cfg(feature = "recording")]
+    pub fn resume_from(
+        self,
+        path: impl AsRef<std::path::Path>,
+    ) -> std::io::Result<Checkpointed<M, S, A>>
+    where
+        S: serde::Serialize + serde::de::DeserializeOwned,
+        M::State: serde::Serialize + serde::de::DeserializeOwned + 'static,
+        M::Action: serde::Serialize + serde::de::DeserializeOwned + 'static,
+        A: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.options.supports_checkpoints()?;
+        Checkpointed(self.serialized()).resume_from(path)
+    }
+
+    /// Encode states and actions as JSON, whenever they need to be written to disk.
+    #[cfg(feature = "recording")]
+    fn serialized(mut self) -> Self
+    where
+        S: serde::Serialize + serde::de::DeserializeOwned,
+        M::State: serde::Serialize + serde::de::DeserializeOwned + 'static,
+        M::Action: serde::Serialize + serde::de::DeserializeOwned + 'static,
+        A: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.codecs = Some(Codecs {
+            mapped: Codec::json(),
+            states: Codec::json(),
+            actions: Codec::json(),
+            edges: Codec::json(),
+        });
         self
     }
 
@@ -122,6 +468,19 @@ where
         self
     }
 
+    /// Register a callback to be given a snapshot of the traversal's progress
+    /// every `every` steps, e.g. to drive a progress bar, or to notice the state space
+    /// exploding early on. To receive the snapshots elsewhere, send them over a channel.
+    /// Unlike [`Traversal::trace_every`], this doesn't log anything.
+    pub fn progress(
+        mut self,
+        every: usize,
+        progress: impl Fn(Progress) + Send + Sync + 'static,
+    ) -> Self {
+        self.options.progress = Some((every.max(1), Arc::new(progress)));
+        self
+    }
+
     /// Determine which errors are fatal and which are not.
     /// By default, errors during traversal simply cause the represented edge to be skipped,
     /// allowing other nodes to be explored.
@@ -134,9 +493,127 @@ where
         self
     }
 
+    /// Declare which actions are independent, to enable partial order reduction.
+    /// In each state, only the enabled actions of one class of mutually dependent actions
+    /// are explored, so that the many interleavings of actions which commute, such as
+    /// actions of different nodes of a network, are not all explored.
+    ///
+    /// `independence(a, b, state)` may return true only if, in `state` and every state
+    /// reachable from it, neither of `a` and `b` can enable or disable the other,
+    /// and taking both in either order leads to the same state. It must be symmetric.
+    ///
+    /// This preserves every terminal state, and every deadlock, and the truth of
+    /// invariants and of LTL specifications without the `X` (next) operator, provided
+    /// that any action which can change the truth of a proposition is declared
+    /// dependent on every other action. A state is fully explored whenever a reduced
+    /// set of actions would lead back to a state already visited, so that no action is
+    /// postponed forever around a cycle. Fairness assumptions are not preserved,
+    /// paths and counterexamples need not be shortest, the graph holds only the explored
+    /// edges, and with a depth limit, states may be missed.
+    ///
+    /// Classes are formed from all the actions tried in a state, so if the machine lists
+    /// them (see [`Machine::enabled_actions`]), the list must include any action which
+    /// could become enabled later and depend on one which is enabled.
+    /// Forming them calls `independence` for each action in the class of an enabled one
+    /// with every action tried which is in no class yet, so in the worst case, when
+    /// all of them are dependent, once per pair of actions tried in every state.
+    pub fn independence(
+        mut self,
+        independence: impl Fn(&M::Action, &M::Action, &M::State) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.independence = Some(Arc::new(independence));
+        self
+    }
+
+    /// Add an invariant: a property which must hold in every reachable state.
+    /// Any number of invariants can be added, each with a distinct name,
+    /// and they are checked by [`Traversal::check_invariants`].
+    pub fn invariant(
+        mut self,
+        name: impl ToString,
+        invariant: impl Fn(&M::State) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.invariants
+            .push((name.to_string(), Arc::new(invariant)));
+        self
+    }
+
+    /// Record how often each kind of action succeeds and fails, and which errors it returns,
+    /// in [`TraversalReport::coverage`]. `kind(action)` groups the actions, e.g.
+    /// `|a| format!("{a:?}")` to count every action separately, or the name of the variant
+    /// of an enum of actions, to count each variant as a whole.
+    ///
+    /// Each action is counted every time it is tried, which is once per state it is tried in,
+    /// unless a state is explored again, as with [`Strategy::IterativeDeepening`].
+    /// Every kind of action tried when the machine lists none (see
+    /// [`Traversal::with_enabled_actions`]), which is every possible action if the actions
+    /// are [`Exhaustive`], appears in the coverage, even if it is never tried.
+    pub fn coverage(mut self, kind: impl Fn(&M::Action) -> String + Send + Sync + 'static) -> Self {
+        self.coverage = Some(Arc::new(kind));
+        self
+    }
+
+    /// In a simulation (see [`Traversal::simulate`]), try arbitrary actions in each state,
+    /// generated by proptest, rather than the actions listed for it.
+    pub fn arbitrary_actions(mut self) -> Self
+    where
+        M::Action: Arbitrary,
+    {
+        // The strategy is made afresh each time, since it need not be Send
+        self.arbitrary_action = Some(Arc::new(|runner| {
+            runner
+                .generate()
+                .unwrap_or_else(|reason| panic!("could not generate an action: {reason}"))
+        }));
+        self
+    }
+
+    /// In a simulation (see [`Traversal::simulate`]), try actions generated by the given
+    /// proptest strategy in each state, rather than the actions listed for it.
+    pub fn arbitrary_actions_with(
+        mut self,
+        strategy: impl ValueStrategy<Value = M::Action> + Send + Sync + 'static,
+    ) -> Self {
+        self.arbitrary_action = Some(Arc::new(move |runner| {
+            runner
+                .generate_with(&strategy)
+                .unwrap_or_else(|reason| panic!("could not generate an action: {reason}"))
+        }));
+        self
+    }
+
+    /// Treat states which are relabelings of each other under any permutation of the
+    /// values of the [`Id`] type `I` as the same state, so that only one of them is explored.
+    /// Each state is recorded as visited by its lexicographically smallest relabeling
+    /// (see [`Symmetry::canonical`]), which is then passed to any mapping set by
+    /// [`Traversal::map_state`], whether it is set before or after this.
+    ///
+    /// Each action taken in a state is relabeled by the same permutation as the state,
+    /// so that the edges of the graph, and the steps of paths through it, are actions
+    /// which can be taken in the canonical states they come from. Each leads to
+    /// a relabeling of the state it is an edge to, which need not be that state itself.
+    ///
+    /// This cuts up to N! equivalent states for an `Id` type with N values, at the cost of
+    /// trying all N! relabelings of every state visited. It is only sound if the machine
+    /// treats all values of `I` alike, and when model checking, if every proposition does too.
+    pub fn symmetry<I: Id>(mut self) -> Self
+    where
+        M::State: Relabel<I> + Ord,
+        M::Action: Relabel<I>,
+    {
+        let symmetry = Arc::new(Symmetry::<I>::full());
+        let relabel = symmetry.clone();
+        self.symmetry = Some(Canonicalizer {
+            state: Arc::new(move |s| symmetry.canonicalize(s)),
+            action: Arc::new(move |a, permutation| relabel.relabel(a, permutation)),
+        });
+        self
+    }
+
     /// When recording a state as visited, map it to a different type.
     /// This is how to specify symmetry groups: Any distinct states which map to the same
     /// value are considered symmetric, so they don't need to be explored separately.
+    /// For symmetry under permutations of an [`Id`] type, see [`Traversal::symmetry`].
     pub fn map_state(
         mut self,
         map_state: impl Fn(M::State) -> Option<S> + Send + Sync + 'static,
@@ -151,23 +628,144 @@ where
         mut self,
         map_action: impl Fn(M::Action) -> Option<A> + Send + Sync + 'static,
     ) -> Self {
-        self.map_action = Arc::new(map_action);
+        self.map_action = Arc::new(move |_, a| map_action(a));
         self
     }
 
+    /// Run the traversal to completion, or until it goes over budget
+    /// (see [`Traversal::max_states`]), and return a report of it.
+    pub fn run(self) -> Result<TraversalReport, M::Error> {
+        Ok(traverse(self, false, false)?.report)
+    }
+
     /// Run the traversal until a terminal state is reached, and return
     /// all terminal states found.
     pub fn run_terminal(self) -> Result<TerminalSet<S>, M::Error> {
-        let (_report, _, terminals) = traverse(self, false, true)?;
-        Ok(terminals.unwrap())
+        Ok(traverse(self, false, true)?.terminals.unwrap())
     }
 
     /// Return a graph of the traversed state machine.
     /// This can be fed to [`diagram::write_dot`] to generate a graphviz dot file,
     /// which can be visualized.
+    ///
+    /// If the traversal goes over budget (see [`Traversal::max_states`]),
+    /// the graph built so far is returned.
     pub fn diagram(self) -> Result<DiGraph<S, A>, M::Error> {
-        let (_report, graph, _) = traverse(self, true, false)?;
-        Ok(graph.unwrap())
+        Ok(traverse(self, true, false)?.graph.unwrap())
+    }
+
+    /// Check every reachable state against each invariant (see [`Traversal::invariant`]),
+    /// and return a violation for each invariant which does not hold, in the order in which
+    /// they were added. The traversal does not stop at a violation, so that every violated
+    /// invariant is found, and no path needs to be stored in the states.
+    ///
+    /// Each violation comes with a shortest path from an initial state to a violating state,
+    /// through the graph of the traversal, whose states are mapped (see
+    /// [`Traversal::map_state`]). With [`Traversal::independence`], a path is only
+    /// shortest among the actions explored.
+    pub fn check_invariants(self) -> Result<Vec<InvariantViolation<S, M::Action>>, M::Error>
+    where
+        M::Action: Hash + Eq,
+    {
+        let check_deadlock = self.options.check_deadlock;
+        let names = self
+            .invariants
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect_vec();
+        let (traversed, graph, paths) = self.traverse_with_paths()?;
+        Ok(violations(
+            &traversed,
+            &graph,
+            &paths,
+            names,
+            check_deadlock,
+        ))
+    }
+
+    /// Search for a reachable state which matches the predicate, stopping as soon as one
+    /// is found, and return a path to it, or None if there is no such state.
+    /// This is how to ask whether something can ever happen.
+    ///
+    /// The path is a shortest one through the part of the graph traversed before stopping,
+    /// so with [`Strategy::Bfs`] or [`Strategy::IterativeDeepening`], the state is one of
+    /// the closest matching states to an initial state, and the path is a shortest path.
+    /// As with [`Traversal::check_invariants`], the states along the path are mapped
+    /// (see [`Traversal::map_state`]), so that with [`Traversal::symmetry`],
+    /// a state is found if any relabeling of it is reachable.
+    pub fn find(
+        self,
+        predicate: impl Fn(&M::State) -> bool + Send + Sync + 'static,
+    ) -> Result<Option<Witness<S, M::Action>>, M::Error>
+    where
+        M::Action: Hash + Eq,
+    {
+        Ok(self.find_all(predicate, 1)?.into_iter().next())
+    }
+
+    /// Search for reachable states which match the predicate, stopping once `limit`
+    /// of them have been found, and return a path to each, closest first.
+    /// Pass `usize::MAX` to find every such state. See [`Traversal::find`].
+    ///
+    /// With a parallel strategy, a few more than `limit` may be found by threads
+    /// which were visiting states when the search stopped, which are all returned.
+    pub fn find_all(
+        mut self,
+        predicate: impl Fn(&M::State) -> bool + Send + Sync + 'static,
+        limit: usize,
+    ) -> Result<Vec<Witness<S, M::Action>>, M::Error>
+    where
+        M::Action: Hash + Eq,
+    {
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+        self.target = Some((Arc::new(predicate), limit));
+        let (traversed, graph, paths) = self.traverse_with_paths()?;
+        let mut found = traversed
+            .found
+            .into_iter()
+            .filter_map(|ix| Some((*paths.order.get(&ix)?, ix)))
+            .collect_vec();
+        found.sort();
+        Ok(found
+            .into_iter()
+            .map(|(_, ix)| {
+                let (initial, path) = paths.witness(&graph, ix);
+                Witness { initial, path }
+            })
+            .collect())
+    }
+
+    /// Traverse, recording the action of each edge in the graph,
+    /// and find shortest paths through it from the initial states.
+    #[allow(clippy::type_complexity)]
+    fn traverse_with_paths(
+        self,
+    ) -> Result<
+        (
+            Traversed<S, M::Action>,
+            DiGraph<S, M::Action>,
+            ShortestPaths,
+        ),
+        M::Error,
+    >
+    where
+        M::Action: Hash + Eq,
+    {
+        let initial = self
+            .initial
+            .iter()
+            .filter_map(|s| self.mapped(s.clone()))
+            .collect_vec();
+        let traversal = self.map_edges(|_, a| Some(a), |codecs| codecs.actions.clone());
+        let mut traversed = traverse(traversal, true, false)?;
+        let graph = traversed.graph.take().unwrap();
+
+        let nodes: HashMap<_, _> = graph.node_indices().map(|ix| (&graph[ix], ix)).collect();
+        let paths =
+            ShortestPaths::new(&graph, initial.iter().filter_map(|s| nodes.get(s).copied()));
+        Ok((traversed, graph, paths))
     }
 }
 
@@ -186,6 +784,11 @@ where
     /// This causes a Buchi automaton to be built from the specification,
     /// which adds additional guards to the state machine. It also sets the
     /// Traversal with the appropriate settings for model checking.
+    ///
+    /// Since this changes the type of the states, it fails if
+    /// [`Traversal::spill_to_disk`] has already been called: call it after this,
+    /// as well as [`Traversal::checkpoint`] and [`Traversal::resume_from`].
+    #[allow(clippy::type_complexity)]
     pub fn specced<P>(
         self,
         props: P,
@@ -195,6 +798,12 @@ where
         P: PropositionMapping + Send + Sync + 'static,
         Transition<M>: EvaluatePropositions<P::Proposition>,
     {
+        if self.codecs.is_some() {
+            anyhow::bail!(
+                "spill_to_disk must be called after specced, \
+                 since it changes the type of the states"
+            );
+        }
         let machine = ModelChecker::from_ltl(self.machine, props, ltl)?;
         let initial = self
             .initial
@@ -207,60 +816,254 @@ where
         Ok(Traversal {
             machine,
             initial,
-            max_depth: self.max_depth,
-            trace_every: self.trace_every,
-            trace_errors: self.trace_errors,
-            ignore_loopbacks: self.ignore_loopbacks,
+            options: self.options,
+            codecs: None,
+            resumed: None,
             visitor: Arc::new(move |s, visit| {
-                visitor(&*s, visit).map_err(ModelCheckerTransitionError::MachineError)
+                visitor(s, visit).map_err(ModelCheckerTransitionError::MachineError)
             }),
             is_fatal_error: Arc::new(|e| {
                 !matches!(e, ModelCheckerTransitionError::MachineError(_))
             }),
+            all_actions: self.all_actions,
+            arbitrary_action: self.arbitrary_action,
+            independence: self.independence.map(|independence| {
+                Arc::new(move |a: &_, b: &_, s: &ModelCheckerState<_, _>| {
+                    independence(a, b, &s.pathstate.state)
+                }) as Arc<_>
+            }),
+            invariants: self
+                .invariants
+                .into_iter()
+                .map(|(name, invariant)| {
+                    let invariant =
+                        Arc::new(move |s: &ModelCheckerState<_, _>| invariant(&s.pathstate.state))
+                            as Arc<_>;
+                    (name, invariant)
+                })
+                .collect(),
+            coverage: self.coverage,
+            target: self.target.map(|(target, limit)| {
+                let target = Arc::new(move |s: &ModelCheckerState<_, _>| target(&s.pathstate.state))
+                    as Arc<_>;
+                (target, limit)
+            }),
+            symmetry: self.symmetry.map(|symmetry| {
+                let canonical = symmetry.state;
+                Canonicalizer {
+                    state: Arc::new(move |s: &ModelCheckerState<_, _>| {
+                        let (state, permutation) = canonical(&s.pathstate.state);
+                        let mut s = s.clone();
+                        s.pathstate.state = state;
+                        (s, permutation)
+                    }),
+                    action: symmetry.action,
+                }
+            }),
             map_state: Arc::new(move |s| s.map_state(|ss| (map_state)(ss))),
-            map_action: Arc::new(move |a| (map_action)(a)),
+            map_action: Arc::new(move |s, a| (map_action)(&s.pathstate.state, a)),
         })
     }
 }
 
+impl<M: Machine, S, A> Traversal<M, S, A> {
+    /// A state as recorded as visited: its canonical relabeling under symmetry, if any
+    /// (see [`Traversal::symmetry`]), mapped by [`Traversal::map_state`].
+    /// Also returns the permutation giving the canonical relabeling, with which to
+    /// relabel the actions taken in the state (see [`Traversal::relabel_action`]).
+    fn map_state_canonical(&self, state: M::State) -> (Option<S>, usize) {
+        match &self.symmetry {
+            Some(symmetry) => {
+                let (state, permutation) = (symmetry.state)(&state);
+                ((self.map_state)(state), permutation)
+            }
+            None => ((self.map_state)(state), 0),
+        }
+    }
+
+    /// A state as recorded as visited (see [`Traversal::map_state_canonical`]).
+    fn mapped(&self, state: M::State) -> Option<S> {
+        self.map_state_canonical(state).0
+    }
+
+    /// An action taken in a state, relabeled by the permutation which gives the state's
+    /// canonical relabeling, if there is symmetry.
+    fn relabel_action(&self, action: &M::Action, permutation: usize) -> M::Action {
+        match &self.symmetry {
+            Some(symmetry) => (symmetry.action)(action, permutation),
+            None => action.clone(),
+        }
+    }
+
+    /// The actions to try in a state: those listed by the machine, or else every possible
+    /// action, which is listed in `all_actions` once needed.
+    fn actions_in<'a>(
+        &self,
+        state: &M::State,
+        all_actions: &'a OnceLock<Vec<M::Action>>,
+    ) -> Cow<'a, [M::Action]> {
+        if let Some(actions) = self.machine.enabled_actions(state) {
+            return Cow::Owned(actions.collect());
+        }
+        Cow::Borrowed(all_actions.get_or_init(self.all_actions))
+    }
+
+    /// Replace the edge mapping, which changes the type of the graph's edges,
+    /// along with the codec for them, if there is one.
+    fn map_edges<AA>(
+        self,
+        map_action: impl Fn(&M::State, M::Action) -> Option<AA> + Send + Sync + 'static,
+        edge_codec: impl FnOnce(&TraversalCodecs<M, S, A>) -> Codec<AA>,
+    ) -> Traversal<M, S, AA> {
+        Traversal {
+            machine: self.machine,
+            initial: self.initial,
+            options: self.options,
+            codecs: self.codecs.map(|codecs| {
+                let edges = edge_codec(&codecs);
+                codecs.with_edges(edges)
+            }),
+            resumed: self.resumed,
+            visitor: self.visitor,
+            is_fatal_error: self.is_fatal_error,
+            all_actions: self.all_actions,
+            arbitrary_action: self.arbitrary_action,
+            independence: self.independence,
+            invariants: self.invariants,
+            coverage: self.coverage,
+            target: self.target,
+            symmetry: self.symmetry,
+            map_state: self.map_state,
+            map_action: Arc::new(map_action),
+        }
+    }
+}
+
 impl<M, S, A, P> Traversal<ModelChecker<M, P>, ModelCheckerState<S, M::Action>, A>
 where
     M: Machine + Send + Sync + 'static,
     M::State: Clone + Debug + Eq + Hash + Send + Sync + 'static,
     S: Clone + Debug + Eq + Hash + Send + Sync + 'static,
-    M::Action: Clone + Debug + Eq + Hash + Exhaustive + Send + Sync + 'static,
+    M::Action: Clone + Debug + Eq + Hash + Send + Sync + 'static,
     A: Clone + Debug + Eq + Hash + Exhaustive + Send + Sync + 'static,
     M::Error: Debug + Send + Sync + 'static,
     P: PropositionMapping + Send + Sync + 'static,
     Transition<M>: EvaluatePropositions<P::Proposition>,
 {
+    /// Assume weak fairness for the actions matching the predicate when checking liveness:
+    /// paths along which some such action is enabled continuously from some point on,
+    /// but never taken, are not counterexamples.
+    ///
+    /// Call this once per class of actions which should be treated fairly,
+    /// e.g. once for each node of a distributed system.
+    pub fn weak_fairness(
+        mut self,
+        actions: impl Fn(&M::Action) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.machine.fairness.push(Fairness::weak(actions));
+        self
+    }
+
+    /// Assume strong fairness for the actions matching the predicate when checking liveness:
+    /// paths along which some such action is enabled infinitely often,
+    /// but taken only finitely often, are not counterexamples.
+    ///
+    /// Call this once per class of actions which should be treated fairly.
+    pub fn strong_fairness(
+        mut self,
+        actions: impl Fn(&M::Action) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.machine.fairness.push(Fairness::strong(actions));
+        self
+    }
+
     /// Do a model check on a traversal on which [`Traversal::specced`] has been called.
     /// This returns a report if the model check succeeds, or any errors if it fails.
     ///
+    /// Safety violations are found during the traversal, and the path to one is a shortest
+    /// such path unless [`Strategy::Dfs`] is used. Once the whole state space
+    /// has been traversed, it is searched for a cycle which violates the specification,
+    /// which is reported as a [`Lasso`](crate::model_checker::Lasso). Only cycles which respect
+    /// the fairness assumptions (see [`Traversal::weak_fairness`]) are considered.
+    /// Loopbacks are never ignored while model checking, since they may form such a cycle.
+    /// With [`Traversal::check_deadlock`], a shortest path to a deadlock is reported first.
+    ///
+    /// If the traversal goes over budget (see [`Traversal::max_states`]), any counterexample
+    /// found is still genuine, but success only means that none was found among the states
+    /// explored, which is flagged by [`TraversalReport::incomplete`].
+    ///
     /// For a more easily readable report, see [`Traversal::model_check_report`].
     pub fn model_check(self) -> Result<TraversalReport, ModelCheckerError<M>> {
-        match traverse(self, true, false) {
-            Ok((report, graph, _)) => {
-                let condensed = petgraph::algo::condensation(graph.unwrap(), true);
-
-                let leaves = condensed.node_indices().filter(|n| {
-                    let outgoing = condensed
-                        .neighbors_directed(*n, petgraph::Direction::Outgoing)
-                        .count();
-                    outgoing == 0
-                });
-
-                for index in leaves {
-                    let scc = condensed.node_weight(index).unwrap();
-                    let accepting = scc.iter().any(|n| n.buchi.is_accepting());
-                    if !accepting {
-                        let mut paths = scc.iter().map(|n| n.pathstate.path.clone()).collect_vec();
-                        paths.sort_by_key(|p| p.len());
-                        return Err(ModelCheckerError::Liveness { paths });
+        let negation = self.machine.negation.clone();
+        let fairness = self.machine.fairness.clone();
+        let initial = self
+            .initial
+            .iter()
+            .filter_map(|s| Some((self.mapped(s.clone())?, s.pathstate.state.clone())))
+            .collect_vec();
+        let check_deadlock = self.options.check_deadlock;
+        let mut traversal = self.map_edges(
+            |s: &ModelCheckerState<M::State, M::Action>, action| {
+                Some(LivenessEdge {
+                    action,
+                    next: s.pathstate.state.clone(),
+                    valuation: s.valuation.clone(),
+                })
+            },
+            // An edge is encoded as its action and the checker state it leads to,
+            // which holds the valuation
+            |codecs| {
+                codecs.actions.clone().pair(codecs.states.clone()).map(
+                    |edge: &LivenessEdge<M>| {
+                        let mut next = ModelCheckerState::new(edge.next.clone(), []);
+                        next.valuation = edge.valuation.clone();
+                        (edge.action.clone(), next)
+                    },
+                    |(action, next)| LivenessEdge {
+                        action,
+                        next: next.pathstate.state,
+                        valuation: next.valuation,
+                    },
+                )
+            },
+        );
+        traversal.options.ignore_loopbacks = false;
+
+        match traverse(traversal, true, false) {
+            Ok(Traversed {
+                report,
+                graph,
+                deadlocks,
+                ..
+            }) => {
+                let graph = graph.unwrap();
+                let nodes: HashMap<_, _> =
+                    graph.node_indices().map(|ix| (&graph[ix], ix)).collect();
+                let initial = initial
+                    .into_iter()
+                    .filter_map(|(mapped, s)| Some((*nodes.get(&mapped)?, s)))
+                    .collect_vec();
+
+                if check_deadlock {
+                    let paths = ShortestPaths::new(&graph, initial.iter().map(|(ix, _)| *ix));
+                    if let Some(ix) = paths.closest(deadlocks) {
+                        let (start, path) = paths.path_to(&graph, ix);
+                        let initial = initial
+                            .into_iter()
+                            .find_map(|(ix, s)| (ix == start).then_some(s))
+                            .expect("paths start from an initial node");
+                        let path = path
+                            .into_iter()
+                            .map(|(edge, _)| (edge.action, edge.next))
+                            .collect();
+                        return Err(ModelCheckerError::Deadlock { initial, path });
                     }
                 }
 
-                Ok(report)
+                match find_lasso(&negation, &graph, &initial, &fairness) {
+                    Some(lasso) => Err(ModelCheckerError::Liveness { lasso }),
+                    None => Ok(report),
+                }
             }
             Err(e) => match e {
                 ModelCheckerTransitionError::BuchiError(e) => Err(ModelCheckerError::Safety {
@@ -297,10 +1100,21 @@ where
                         println!("failing state: {cur:#?}");
                         println!("next state: {next:#?}");
                     }
-                    ModelCheckerError::Liveness { paths } => {
+                    ModelCheckerError::Liveness { lasso } => {
                         println!("Model checker liveness check failed.");
                         println!();
-                        println!("paths: {paths:#?}");
+                        println!("initial state: {:#?}", lasso.initial);
+                        println!();
+                        println!("prefix: {:#?}", lasso.prefix);
+                        println!();
+                        println!("cycle, repeating forever: {:#?}", lasso.cycle);
+                    }
+                    ModelCheckerError::Deadlock { initial, path } => {
+                        println!("Model checker deadlock check failed.");
+                        println!();
+                        println!("initial state: {initial:#?}");
+                        println!();
+                        println!("path to deadlock: {path:#?}");
                     }
                 }
                 Err("model checker error".into())
@@ -309,6 +1123,52 @@ where
     }
 }
 
+/// A reachable state which violates an invariant (see [`Traversal::check_invariants`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvariantViolation<S, A> {
+    /// The name of the invariant.
+    pub name: String,
+    /// The state the path starts from.
+    pub initial: S,
+    /// The steps leading from the initial state to the violating state,
+    /// which is the last one, or the initial state if there are none.
+    pub path: Vec<(A, S)>,
+}
+
+impl<S, A> InvariantViolation<S, A> {
+    /// The state which violates the invariant.
+    pub fn state(&self) -> &S {
+        self.path.last().map_or(&self.initial, |(_, s)| s)
+    }
+}
+
+/// A path to a reachable state found by [`Traversal::find`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness<S, A> {
+    /// The state the path starts from.
+    pub initial: S,
+    /// The steps leading from the initial state to the state found,
+    /// which is the last one, or the initial state if there are none.
+    pub path: Vec<(A, S)>,
+}
+
+impl<S, A> Witness<S, A> {
+    /// The state which was found.
+    pub fn state(&self) -> &S {
+        self.path.last().map_or(&self.initial, |(_, s)| s)
+    }
+
+    /// The actions along the path.
+    pub fn actions(&self) -> impl Iterator<Item = &A> {
+        self.path.iter().map(|(a, _)| a)
+    }
+
+    /// The states along the path, beginning with the initial state.
+    pub fn states(&self) -> impl Iterator<Item = &S> {
+        std::iter::once(&self.initial).chain(self.path.iter().map(|(_, s)| s))
+    }
+}
+
 /// Specifies some context about a visit to a state
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum VisitType {
@@ -316,8 +1176,14 @@ pub enum VisitType {
     Normal,
     /// The state is a terminal state
     Terminal,
-    /// The state is the last in a loop
+    /// The state is not terminal, but every action which can be taken in it
+    /// leads back to itself, so it loops forever.
+    /// When exploring, the state has already been visited as [`VisitType::Normal`].
     LoopTerminal,
+    /// The state is not terminal, but no action can be taken in it
+    /// (see [`Traversal::check_deadlock`]).
+    /// When exploring, the state has already been visited as [`VisitType::Normal`].
+    Deadlock,
 }
 
 /// Information about a successfully completed traversal
@@ -327,307 +1193,1072 @@ pub struct TraversalReport {
     pub num_visited: usize,
     /// Total states that were terminal (TODO: revisit actual meaning of "terminal", see other todos)
     pub num_terminations: usize,
+    /// Total states that were not terminal, but in which no action could be taken
+    pub num_deadlocks: usize,
     /// Total edges skipped due to errors
     pub num_edges_skipped: usize,
+    /// Total edges not explored due to partial order reduction
+    /// (see [`Traversal::independence`])
+    pub num_edges_pruned: usize,
     /// Total iterations taken
     pub total_steps: usize,
     /// Maximum graph depth reached
     pub max_depth: usize,
+    /// Total states written to disk (see [`Traversal::spill_to_disk`])
+    pub num_spilled: usize,
+    /// The estimated probability that some reachable state was not visited,
+    /// because it was mistaken for a state already visited (see [`Traversal::compaction`]).
+    /// This is `None` if every visited state was remembered exactly.
+    pub omission_probability: Option<f64>,
     /// Time taken
     pub time_taken: std::time::Duration,
+    /// How often each kind of action succeeded and failed, if recorded
+    /// (see [`Traversal::coverage`])
+    pub coverage: Option<Coverage>,
+    /// How many states were found and explored at each depth, indexed by depth
+    pub depths: Vec<DepthStats>,
+    /// Why the traversal stopped before exploring every reachable state,
+    /// if it went over budget (see [`Traversal::max_states`]),
+    /// in which case the rest of the report only covers the states explored.
+    pub incomplete: Option<Incomplete>,
 }
 
-/// Somewhat messy function that performs the traversal.
-///
-/// This function is the core of the model checker as well as the diagram generator.
-fn traverse<M, S, A>(
-    traversal: Traversal<M, S, A>,
-    do_graphing: bool,
-    record_terminals: bool,
-) -> Result<
-    (
-        TraversalReport,
-        Option<DiGraph<S, A>>,
-        Option<TerminalSet<S>>,
-    ),
-    M::Error,
->
-where
-    M: Machine + Send + Sync + 'static,
-    M::State: Cog + 'static,
-    M::Action: Exhaustive + Cog + 'static,
-    M::Error: Debug + Send + Sync + 'static,
-    S: Cog + Hash + Eq + 'static,
-    A: Cog + Hash + Eq + 'static,
-{
-    let machine = traversal.machine;
-    let initial = traversal.initial;
-    let max_depth = traversal.max_depth;
-    let trace_every = traversal.trace_every;
-    let ignore_loopbacks = traversal.ignore_loopbacks;
-    let visitor = traversal.visitor;
-    let is_fatal_error = traversal.is_fatal_error;
-    let trace_errors = traversal.trace_errors;
-    let map_state = traversal.map_state;
-    let map_action = traversal.map_action;
-
-    let terminals: Arc<Mutex<TerminalSet<S>>> = Arc::new(Mutex::new(HashSet::new()));
-    // let loop_terminals: Arc<Mutex<TerminalSet<S>>> = Arc::new(Mutex::new(HashSet::new()));
-    let visited_states: Arc<Mutex<HashMap<S, NodeIndex>>> = Arc::new(Mutex::new(HashMap::new()));
-    let visited_edges: Arc<Mutex<HashSet<(NodeIndex, NodeIndex, A)>>> =
-        Arc::new(Mutex::new(HashSet::new()));
+impl TraversalReport {
+    /// The average number of successful transitions from each state explored.
+    pub fn branching_factor(&self) -> f64 {
+        let total = self
+            .depths
+            .iter()
+            .fold(DepthStats::default(), |total, depth| DepthStats {
+                new_states: total.new_states + depth.new_states,
+                expanded: total.expanded + depth.expanded,
+                successors: total.successors + depth.successors,
+            });
+        total.branching_factor()
+    }
+}
 
-    let graph = Arc::new(Mutex::new(DiGraph::new()));
+/// The order in which a [`Traversal`] explores the state graph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// Level-synchronous breadth-first search: every state at one depth is visited
+    /// before any state at the next depth, so each state is first reached by a shortest
+    /// path, and any counterexample found is a shortest one.
+    /// The states of each level are visited in parallel (see [`Traversal::threads`]).
+    #[default]
+    Bfs,
+
+    /// Each thread explores depth-first from its own queue of states, and steals states
+    /// from the other threads' queues whenever its own runs out.
+    /// Without waiting for each level to be completed, this is the fastest way to explore
+    /// a whole state space on many cores, but paths and counterexamples need not be shortest.
+    Parallel,
+
+    /// Depth-first search, on a single thread.
+    /// Only the unexplored siblings of the current path are queued, rather than
+    /// a whole level of the graph, and states are visited in the order which
+    /// nested-DFS style algorithms expect.
+    Dfs,
+
+    /// Depth-first searches with a depth limit of 0, 1, 2, ..., until a search
+    /// reaches no state beyond the limit, or the limit reaches [`Traversal::max_depth`].
+    /// Like [`Strategy::Bfs`], this finds shortest paths and counterexamples,
+    /// but with the memory use of [`Strategy::Dfs`],
+    /// at the cost of visiting shallow states again on each iteration,
+    /// so the visitor may be called more than once for the same state.
+    IterativeDeepening,
+}
 
-    let seen = Arc::new(crossbeam::queue::SegQueue::new());
+/// How a [`Traversal`] remembers which states it has visited.
+///
+/// The lossy options are for quick sweeps of state spaces too large to explore exactly:
+/// a state may be mistaken for one already visited, in which case it and possibly
+/// the states beyond it are never explored. Any violation which is found is still real,
+/// and a model checking counterexample still comes with its full path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Compaction {
+    /// Remember every visited state in full, so that nothing is missed.
+    #[default]
+    Exact,
+
+    /// Remember only a 64-bit fingerprint of each visited state (hash compaction).
+    /// Unless there are billions of states, a collision is very unlikely.
+    HashCompaction,
+
+    /// Set a few bits for each visited state in an array of `2^log2_bits` bits
+    /// (bitstate hashing), which uses a fixed amount of memory however many states
+    /// there are, but misses more states as the array fills up.
+    /// The shallowest depth of each state is not remembered, so with a depth limit,
+    /// states reached again by a shorter path are not explored further.
+    ///
+    /// A graph needs to know which node each state is, so a traversal which builds one,
+    /// including model checking, uses [`Compaction::HashCompaction`] instead.
+    Bitstate {
+        /// The base-2 logarithm of the number of bits, e.g. 32 for 512 MiB
+        log2_bits: u32,
+        /// The number of bits set per state, typically 2 or 3
+        hashes: u32,
+    },
+}
 
-    let (err_tx, err_rx) = crossbeam::channel::bounded(1);
+/// Relabels states to their canonical form under symmetry, and actions alike
+/// (see [`Traversal::symmetry`])
+#[allow(clippy::type_complexity)]
+struct Canonicalizer<St, Ac> {
+    /// The canonical relabeling of a state, with the index of the permutation giving it
+    state: Arc<dyn Fn(&St) -> (St, usize) + Send + Sync>,
+    /// An action relabeled by the permutation with the given index
+    action: Arc<dyn Fn(&Ac, usize) -> Ac + Send + Sync>,
+}
 
-    for state in initial {
-        seen.push((state, Option::<(NodeIndex, M::Action)>::None, 0));
+// Implemented by hand, since deriving would require the states and actions to be Clone
+impl<St, Ac> Clone for Canonicalizer<St, Ac> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            action: self.action.clone(),
+        }
     }
+}
 
-    let stop = Arc::new(AtomicBool::new(false));
-    let total_steps = Arc::new(AtomicUsize::new(0));
-    let num_seen = Arc::new(AtomicUsize::new(0));
-    let num_terminations = Arc::new(AtomicUsize::new(0));
-    let num_edges_skipped = Arc::new(AtomicUsize::new(0));
-    let max_depth_seen = Arc::new(AtomicUsize::new(0));
-
-    tracing::info!("traversal starting");
-    let all_actions: im::Vector<_> = M::Action::iter_exhaustive(None).collect();
+/// A state waiting to be visited, with the node and action it was reached from,
+/// and its depth
+type Queued<M> = (
+    <M as Machine>::State,
+    Option<(NodeIndex, <M as Machine>::Action)>,
+    usize,
+);
+
+/// Codecs for the types involved in traversing a machine `M`
+type TraversalCodecs<M, S, A> = Codecs<S, <M as Machine>::State, <M as Machine>::Action, A>;
+
+/// Everything found by a traversal
+struct Traversed<S, A> {
+    report: TraversalReport,
+    graph: Option<DiGraph<S, A>>,
+    terminals: Option<TerminalSet<S>>,
+    /// The index of each invariant violated in some state, with the node of that state
+    violations: Vec<(usize, NodeIndex)>,
+    /// The node of each deadlocked state
+    deadlocks: Vec<NodeIndex>,
+    /// The node of each state found to match the target
+    found: Vec<NodeIndex>,
+}
 
-    let start_time = std::time::Instant::now();
+/// Shortest paths through a graph from a set of initial nodes, found breadth-first
+struct ShortestPaths {
+    /// The order in which each node reachable from the initial nodes was reached,
+    /// which is in order of distance
+    order: HashMap<NodeIndex, usize>,
+    /// The edge by which each node was first reached
+    parents: HashMap<NodeIndex, (NodeIndex, EdgeIndex)>,
+}
 
-    let task = {
-        let graph = graph.clone();
-        let stop = stop.clone();
-        let total_steps = total_steps.clone();
-        let num_seen = num_seen.clone();
-        let num_terminations = num_terminations.clone();
-        let num_edges_skipped = num_edges_skipped.clone();
-        let max_depth_seen = max_depth_seen.clone();
-        let visited = visited_states.clone();
-        let terminals = terminals.clone();
-        // let loop_terminals = loop_terminals.clone();
-
-        let active_threads = AtomicUsize::new(0);
-        let prev_trace = Mutex::new(IterTrace::default());
-        let trace_every = trace_every.unwrap_or(usize::MAX);
-
-        move |thread_index: usize| {
-            if thread_index > 0 {
-                // Give the seen queue time to fill up
-                // XXX: this is a hack to avoid starvation, could be more robust
-                while seen.len() < 1000 {
-                    if stop.load(SeqCst) {
-                        // The first thread already completed all work
-                        return Ok(());
-                    }
-                    std::thread::sleep(std::time::Duration::from_millis(10));
+impl ShortestPaths {
+    fn new<S, A>(graph: &DiGraph<S, A>, initial: impl IntoIterator<Item = NodeIndex>) -> Self {
+        let mut order: HashMap<NodeIndex, usize> = HashMap::new();
+        let mut parents: HashMap<NodeIndex, (NodeIndex, EdgeIndex)> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for ix in initial {
+            let len = order.len();
+            if let std::collections::hash_map::Entry::Vacant(e) = order.entry(ix) {
+                e.insert(len);
+                queue.push_back(ix);
+            }
+        }
+        while let Some(ix) = queue.pop_front() {
+            for edge in graph.edges(ix) {
+                let next = edge.target();
+                if !order.contains_key(&next) {
+                    order.insert(next, order.len());
+                    parents.insert(next, (ix, edge.id()));
+                    queue.push_back(next);
                 }
             }
+        }
+        Self { order, parents }
+    }
 
-            active_threads.fetch_add(1, SeqCst);
-
-            while let Some((state, prev_node, depth)) = seen.pop() {
-                if stop.load(SeqCst) {
-                    break;
-                }
-                let iter = total_steps.fetch_add(1, SeqCst);
-
-                if iter > 0 && iter % trace_every == 0 {
-                    let trace = IterTrace {
-                        iter,
-                        queued: num_seen.load(SeqCst),
-                        visited: visited.lock().len(),
-                        depth,
-                    };
-                    let mut prev = prev_trace.lock();
+    /// The reachable node closest to the initial nodes, out of those given
+    fn closest(&self, nodes: impl IntoIterator<Item = NodeIndex>) -> Option<NodeIndex> {
+        nodes
+            .into_iter()
+            .filter_map(|ix| Some((self.order.get(&ix)?, ix)))
+            .min()
+            .map(|(_, ix)| ix)
+    }
 
-                    let queued_diff = trace.queued as isize - prev.queued as isize;
-                    let visited_diff = trace.visited as isize - prev.visited as isize;
+    /// A shortest path to a reachable node, as the initial state it starts from,
+    /// and each edge along the way with the state it leads to
+    #[allow(clippy::type_complexity)]
+    fn witness<S: Clone, A: Clone>(
+        &self,
+        graph: &DiGraph<S, A>,
+        ix: NodeIndex,
+    ) -> (S, Vec<(A, S)>) {
+        let (start, path) = self.path_to(graph, ix);
+        (graph[start].clone(), path)
+    }
 
-                    let queued_diff_str = if queued_diff == 0 {
-                        "0".to_string().white()
-                    } else if queued_diff > 0 {
-                        format!("+{}", queued_diff.human_count_bare()).green()
-                    } else {
-                        format!("{}", queued_diff.human_count_bare()).red()
-                    };
+    /// A shortest path to a reachable node, as the initial node it starts from,
+    /// and each edge along the way with the state it leads to
+    #[allow(clippy::type_complexity)]
+    fn path_to<S: Clone, A: Clone>(
+        &self,
+        graph: &DiGraph<S, A>,
+        mut ix: NodeIndex,
+    ) -> (NodeIndex, Vec<(A, S)>) {
+        let mut path = vec![];
+        while let Some((parent, edge)) = self.parents.get(&ix) {
+            path.push((graph[*edge].clone(), graph[ix].clone()));
+            ix = *parent;
+        }
+        path.reverse();
+        (ix, path)
+    }
+}
 
-                    let visited_diff_str = if visited_diff == 0 {
-                        "0".to_string().white()
-                    } else if visited_diff > 0 {
-                        format!("+{}", visited_diff.human_count_bare()).green()
-                    } else {
-                        format!("{}", visited_diff.human_count_bare()).red()
-                    };
+/// A violation of each named invariant found in a traversal, and of deadlock freedom
+/// if checked, each with a shortest path to the closest state it was found in
+fn violations<S: Clone, A: Clone>(
+    traversed: &Traversed<S, A>,
+    graph: &DiGraph<S, A>,
+    paths: &ShortestPaths,
+    names: Vec<String>,
+    check_deadlock: bool,
+) -> Vec<InvariantViolation<S, A>> {
+    let violation = |name, ix| {
+        let (initial, path) = paths.witness(graph, ix);
+        InvariantViolation {
+            name,
+            initial,
+            path,
+        }
+    };
 
-                    let depth_str = if trace.depth != prev.depth {
-                        format!("depth={depth} ***").underline().bold()
-                    } else {
-                        format!("depth={depth}    ").white()
-                    };
+    let mut violations = (0..names.len()).map(|_| vec![]).collect_vec();
+    for &(i, ix) in &traversed.violations {
+        violations[i].push(ix);
+    }
+    let mut found = names
+        .into_iter()
+        .zip(violations)
+        .filter_map(|(name, nodes)| Some(violation(name, paths.closest(nodes)?)))
+        .collect_vec();
+    if check_deadlock && let Some(ix) = paths.closest(traversed.deadlocks.iter().copied()) {
+        found.push(violation("deadlock".to_string(), ix));
+    }
+    found
+}
+
+/// Somewhat messy function that performs the traversal.
+///
+/// This function is the core of the model checker as well as the diagram generator.
+fn traverse<M, S, A>(
+    mut traversal: Traversal<M, S, A>,
+    do_graphing: bool,
+    record_terminals: bool,
+) -> Result<Traversed<S, A>, M::Error>
+where
+    M: Machine + Send + Sync + 'static,
+    M::State: Cog + 'static,
+    M::Action: Cog + 'static,
+    M::Error: Debug + Send + Sync + 'static,
+    S: Cog + Hash + Eq + 'static,
+    A: Cog + Hash + Eq + 'static,
+{
+    tracing::info!("traversal starting");
+    let start_time = std::time::Instant::now();
+    let all_actions = OnceLock::new();
+    let threads = if traversal.options.deterministic {
+        1
+    } else {
+        traversal
+            .options
+            .threads
+            .unwrap_or_else(rayon::current_num_threads)
+    };
+    let compaction = match traversal.options.compaction {
+        Compaction::Bitstate { .. } if do_graphing => Compaction::HashCompaction,
+        compaction => compaction,
+    };
+    let queued_codec = traversal.codecs.as_ref().map(Codecs::queued);
+    let resumed = traversal.resumed.take().map(Arc::unwrap_or_clone);
+    let elapsed_before = resumed
+        .as_ref()
+        .map_or(Default::default(), |checkpoint| checkpoint.elapsed);
+    let spill_dir = traversal.options.spill.as_ref().map(|spill| {
+        SpillDir::create(&spill.dir).unwrap_or_else(|e| {
+            panic!(
+                "could not create a directory in {:?} to spill to: {e}",
+                spill.dir
+            )
+        })
+    });
+    // Every kind of action appears in the coverage, if every action is known
+    let mut initial_coverage = Coverage::default();
+    if let Some(kind) = &traversal.coverage {
+        for action in (traversal.all_actions)() {
+            initial_coverage.actions.entry(kind(&action)).or_default();
+        }
+    }
+    let explorer = |depth_limit| Explorer {
+        traversal: &traversal,
+        all_actions: &all_actions,
+        depth_limit,
+        do_graphing,
+        record_terminals,
+        visited: Sharded::new_with(threads, |shards| {
+            match (compaction, &traversal.options.spill) {
+                (Compaction::Exact, Some(spill)) => Visited::spilling(
+                    spill_dir.clone().unwrap(),
+                    traversal.codecs.as_ref().unwrap().mapped.clone(),
+                    spill.max_in_memory / shards,
+                ),
+                (Compaction::Exact, None) => Visited::in_memory(),
+                (Compaction::HashCompaction, _) => Visited::fingerprints(),
+                // Each shard has at least one word of bits
+                (Compaction::Bitstate { log2_bits, hashes }, _) => {
+                    Visited::bitstate(((1usize << log2_bits) / shards).max(64), hashes)
+                }
+            }
+        }),
+        spill_dir: spill_dir.clone(),
+        queued_codec: queued_codec.clone(),
+        visited_edges: Sharded::new(threads),
+        graph: Default::default(),
+        terminals: Default::default(),
+        violations: Default::default(),
+        deadlocks: Default::default(),
+        found: Default::default(),
+        coverage: Mutex::new(initial_coverage.clone()),
+        depths: Default::default(),
+        stopped: AtomicBool::new(false),
+        incomplete: Default::default(),
+        num_visited: AtomicUsize::new(0),
+        truncated: AtomicBool::new(false),
+        total_steps: AtomicUsize::new(0),
+        num_queued: AtomicUsize::new(0),
+        num_seen: AtomicUsize::new(0),
+        num_terminations: AtomicUsize::new(0),
+        num_edges_skipped: AtomicUsize::new(0),
+        num_edges_pruned: AtomicUsize::new(0),
+        max_depth_seen: AtomicUsize::new(0),
+        prev_trace: Default::default(),
+        start_time,
+        elapsed_before,
+    };
+    let initial = || {
+        traversal
+            .initial
+            .iter()
+            .map(|state| (state.clone(), None, 0))
+            .collect_vec()
+    };
 
-                    tracing::info!(
-                        "iter={:<5} │ visited={:<8} Δ={:<8} │ queued={:<8} Δ={:<8} │ {}",
-                        trace.iter.human_count_bare().to_string(),
-                        trace.visited.human_count_bare().to_string(),
-                        visited_diff_str,
-                        trace.queued.human_count_bare().to_string(),
-                        queued_diff_str,
-                        depth_str,
-                    );
-                    *prev = trace;
+    let (explorer, previous_steps) = match traversal.options.strategy {
+        Strategy::Bfs => {
+            let explorer = explorer(traversal.options.max_depth);
+            let initial = match resumed {
+                Some(checkpoint) => explorer.restore(checkpoint),
+                None => initial(),
+            };
+            if threads == 1 {
+                explorer.bfs(initial, false)?;
+            } else if threads == rayon::current_num_threads() {
+                explorer.bfs(initial, true)?;
+            } else {
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .expect("failed to build thread pool")
+                    .install(|| explorer.bfs(initial, true))?;
+            }
+            (explorer, 0)
+        }
+        Strategy::Parallel => {
+            let explorer = explorer(traversal.options.max_depth);
+            explorer.parallel(initial(), threads)?;
+            (explorer, 0)
+        }
+        Strategy::Dfs => {
+            let explorer = explorer(traversal.options.max_depth);
+            explorer.dfs(initial())?;
+            (explorer, 0)
+        }
+        Strategy::IterativeDeepening => {
+            let mut previous_steps = 0;
+            let mut limit = 0;
+            loop {
+                let explorer = explorer(Some(limit));
+                explorer.dfs(initial())?;
+                if !explorer.truncated.load(SeqCst)
+                    || explorer.stopped.load(SeqCst)
+                    || Some(limit) == traversal.options.max_depth
+                {
+                    break (explorer, previous_steps);
                 }
+                tracing::debug!("iterative deepening: depth limit {limit} reached");
+                previous_steps += explorer.total_steps.load(SeqCst);
+                limit += 1;
+            }
+        }
+    };
+
+    let report = TraversalReport {
+        time_taken: elapsed_before + std::time::Instant::now().duration_since(start_time),
+        num_visited: explorer.visited.sum(Visited::len),
+        num_terminations: explorer.num_terminations.load(SeqCst),
+        num_deadlocks: explorer.deadlocks.lock().len(),
+        num_edges_skipped: explorer.num_edges_skipped.load(SeqCst),
+        num_edges_pruned: explorer.num_edges_pruned.load(SeqCst),
+        total_steps: previous_steps + explorer.total_steps.load(SeqCst),
+        max_depth: explorer.max_depth_seen.load(SeqCst),
+        num_spilled: spill_dir.map_or(0, |dir| dir.num_spilled.load(SeqCst)),
+        omission_probability: (compaction != Compaction::Exact).then(|| {
+            let expected_omissions = explorer.visited.sum(Visited::expected_omissions);
+            1.0 - (-expected_omissions).exp()
+        }),
+        coverage: traversal
+            .coverage
+            .is_some()
+            .then(|| std::mem::take(&mut *explorer.coverage.lock())),
+        depths: std::mem::take(&mut *explorer.depths.lock()),
+        incomplete: *explorer.incomplete.lock(),
+    };
+    if let Some(reason) = report.incomplete {
+        tracing::warn!(?reason, "traversal stopped before exploring every state");
+    }
+    Ok(Traversed {
+        report,
+        graph: do_graphing.then(|| explorer.graph.into_inner()),
+        terminals: record_terminals.then(|| explorer.terminals.into_inner()),
+        violations: explorer.violations.into_inner(),
+        deadlocks: explorer.deadlocks.into_inner(),
+        found: explorer.found.into_inner(),
+    })
+}
 
-                let mapped_state = if let Some(mapped_state) = map_state(state.clone()) {
-                    mapped_state
+/// The state of a traversal in progress, shared by every search strategy.
+#[allow(clippy::type_complexity)]
+struct Explorer<'t, M: Machine, S, A> {
+    traversal: &'t Traversal<M, S, A>,
+    /// Every possible action, listed only once needed
+    all_actions: &'t OnceLock<Vec<M::Action>>,
+    depth_limit: Option<usize>,
+    do_graphing: bool,
+    record_terminals: bool,
+
+    /// Each visited state, with its node and the shallowest depth it was visited at
+    visited: Sharded<Visited<S>>,
+    spill_dir: Option<Arc<SpillDir>>,
+    queued_codec: Option<Codec<Queued<M>>>,
+    visited_edges: Sharded<HashSet<(NodeIndex, NodeIndex, A)>>,
+    graph: Mutex<DiGraph<S, A>>,
+    terminals: Mutex<TerminalSet<S>>,
+    /// The index of each invariant violated in some state, with the node of that state
+    violations: Mutex<Vec<(usize, NodeIndex)>>,
+    /// The node of each deadlocked state
+    deadlocks: Mutex<Vec<NodeIndex>>,
+    /// The node of each state found to match the target
+    found: Mutex<Vec<NodeIndex>>,
+    /// The outcomes of the actions tried, if coverage is being recorded
+    coverage: Mutex<Coverage>,
+    /// The states found and explored at each depth
+    depths: Mutex<Vec<DepthStats>>,
+    /// Whether enough states have been found to match the target, or the traversal
+    /// went over budget, so no more are visited
+    stopped: AtomicBool,
+    /// Why the traversal went over budget, if it did
+    incomplete: Mutex<Option<Incomplete>>,
+    /// The number of distinct states visited, kept for the budget
+    num_visited: AtomicUsize,
+
+    /// Whether the depth limit stopped any state from being explored
+    truncated: AtomicBool,
+    total_steps: AtomicUsize,
+    /// The number of states waiting to be visited
+    num_queued: AtomicUsize,
+    num_seen: AtomicUsize,
+    num_terminations: AtomicUsize,
+    num_edges_skipped: AtomicUsize,
+    num_edges_pruned: AtomicUsize,
+    max_depth_seen: AtomicUsize,
+    prev_trace: Mutex<IterTrace>,
+    start_time: std::time::Instant,
+    /// Time taken before resuming from a checkpoint
+    elapsed_before: std::time::Duration,
+}
+
+impl<M, S, A> Explorer<'_, M, S, A>
+where
+    M: Machine,
+    M::Error: Debug + Send + Sync,
+    S: Cog + Hash + Eq,
+    A: Cog + Hash + Eq,
+{
+    /// Visit the states level by level, optionally visiting each level in parallel
+    /// on the current rayon thread pool.
+    /// Each level is visited in chunks, so that a spilled level need not fit in memory.
+    fn bfs(&self, initial: Vec<Queued<M>>, parallel: bool) -> Result<(), M::Error> {
+        self.num_queued.fetch_add(initial.len(), SeqCst);
+        let mut level = self.frontier();
+        level.extend(initial);
+        let mut last_checkpoint = std::time::Instant::now();
+        while !level.is_empty() {
+            let mut next_level = self.frontier();
+            for chunk in level.into_chunks() {
+                let next: Vec<Vec<Queued<M>>> = if !parallel {
+                    chunk
+                        .into_iter()
+                        .map(|queued| self.visit(queued))
+                        .collect::<Result<_, _>>()?
                 } else {
-                    // skip a node with no mapping
-                    continue;
+                    chunk
+                        .into_par_iter()
+                        .map(|queued| self.visit(queued))
+                        .collect::<Result<_, _>>()?
                 };
+                next_level.extend(next.into_iter().flatten());
+            }
+            // Once stopped, the rest of the queue is dropped, so an earlier checkpoint
+            // is the one to resume from
+            if let Some(checkpoint) = &self.traversal.options.checkpoint
+                && last_checkpoint.elapsed() >= checkpoint.every
+                && !self.stopped.load(SeqCst)
+            {
+                let path = &checkpoint.path;
+                match self.write_checkpoint(path, next_level.items_in_memory()) {
+                    Ok(()) => tracing::info!("checkpoint written to {path:?}"),
+                    Err(err) => tracing::error!(?err, "failed to write checkpoint to {path:?}"),
+                }
+                last_checkpoint = std::time::Instant::now();
+            }
+            level = next_level;
+        }
+        Ok(())
+    }
+
+    /// An empty queue of states, which spills to disk if the traversal does.
+    fn frontier(&self) -> Frontier<Queued<M>> {
+        match (
+            &self.traversal.options.spill,
+            &self.spill_dir,
+            &self.queued_codec,
+        ) {
+            (Some(spill), Some(dir), Some(codec)) => {
+                Frontier::spilling(dir.clone(), codec.clone(), spill.max_in_memory)
+            }
+            _ => Frontier::in_memory(),
+        }
+    }
 
-                let (already_seen, node_ix) = {
-                    let mut visited = visited.lock();
-                    match visited.entry(mapped_state.clone()) {
-                        std::collections::hash_map::Entry::Occupied(entry) => (true, *entry.get()),
-                        std::collections::hash_map::Entry::Vacant(entry) => {
-                            max_depth_seen.fetch_max(depth, SeqCst);
-
-                            if do_graphing {
-                                let node_ix = graph.lock().add_node(mapped_state.clone());
-                                entry.insert(node_ix);
-                                (false, node_ix)
-                            } else {
-                                entry.insert(NodeIndex::end());
-                                (false, NodeIndex::end())
+    /// Visit the states on the given number of threads, each with its own queue,
+    /// stealing from each other's queues when they run dry.
+    fn parallel(&self, initial: Vec<Queued<M>>, threads: usize) -> Result<(), M::Error> {
+        self.num_queued.fetch_add(initial.len(), SeqCst);
+        let injector = Injector::new();
+        // The number of states queued or being visited, so that an idle thread
+        // can tell whether any more work may appear
+        let pending = AtomicUsize::new(initial.len());
+        for queued in initial {
+            injector.push(queued);
+        }
+        let workers = (0..threads).map(|_| Worker::new_lifo()).collect_vec();
+        let stealers = workers.iter().map(Worker::stealer).collect_vec();
+        let stop = AtomicBool::new(false);
+        let error = Mutex::new(None);
+
+        std::thread::scope(|scope| {
+            for worker in workers {
+                let (injector, stealers, pending) = (&injector, &stealers, &pending);
+                let (stop, error) = (&stop, &error);
+                scope.spawn(move || {
+                    let backoff = Backoff::new();
+                    while !stop.load(SeqCst) {
+                        let Some(queued) = find_work(&worker, injector, stealers) else {
+                            if pending.load(SeqCst) == 0 {
+                                break;
+                            }
+                            backoff.snooze();
+                            continue;
+                        };
+                        backoff.reset();
+                        match self.visit(queued) {
+                            Ok(next) => {
+                                pending.fetch_add(next.len(), SeqCst);
+                                // Pushed in reverse, so that the first action is popped first
+                                for queued in next.into_iter().rev() {
+                                    worker.push(queued);
+                                }
+                            }
+                            Err(err) => {
+                                error.lock().get_or_insert(err);
+                                stop.store(true, SeqCst);
                             }
                         }
+                        pending.fetch_sub(1, SeqCst);
                     }
-                };
+                });
+            }
+        });
 
-                if do_graphing {
-                    let mut graph = graph.lock();
-                    if let Some((prev_node_ix, edge)) = prev_node {
-                        if let Some(edge) = map_action(edge) {
-                            let ignore = ignore_loopbacks && prev_node_ix == node_ix;
-                            if !ignore
-                                && visited_edges.lock().insert((
-                                    prev_node_ix,
-                                    node_ix,
-                                    edge.clone(),
-                                ))
-                            {
-                                let _ = graph.add_edge(prev_node_ix, node_ix, edge);
-                            }
-                        }
+        match error.into_inner() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Visit the states depth-first, following actions in the order they are enumerated.
+    fn dfs(&self, initial: Vec<Queued<M>>) -> Result<(), M::Error> {
+        self.num_queued.fetch_add(initial.len(), SeqCst);
+        let mut stack = initial;
+        stack.reverse();
+        while let Some(queued) = stack.pop() {
+            stack.extend(self.visit(queued)?.into_iter().rev());
+        }
+        Ok(())
+    }
+
+    /// Visit a single state, recording it in the graph,
+    /// and return the states to visit next.
+    fn visit(&self, (state, prev_node, depth): Queued<M>) -> Result<Vec<Queued<M>>, M::Error> {
+        let Traversal {
+            machine,
+            options:
+                TraversalOptions {
+                    trace_errors,
+                    ignore_loopbacks,
+                    ..
+                },
+            visitor,
+            is_fatal_error,
+            invariants,
+            coverage,
+            target,
+            map_action,
+            ..
+        } = self.traversal;
+
+        self.num_queued.fetch_sub(1, SeqCst);
+
+        // Drain the queues once the search is over
+        if self.stopped.load(SeqCst) {
+            return Ok(vec![]);
+        }
+
+        let iter = self.total_steps.fetch_add(1, SeqCst);
+        self.trace(iter, depth);
+        if let Some(reason) = self.over_budget(iter) {
+            self.stop(reason);
+            return Ok(vec![]);
+        }
+
+        let (mapped_state, permutation) = self.traversal.map_state_canonical(state.clone());
+        let mapped_state = if let Some(mapped_state) = mapped_state {
+            mapped_state
+        } else {
+            // skip a node with no mapping
+            return Ok(vec![]);
+        };
+
+        let (seen, node_ix) = {
+            let mut visited = self.visited.lock(&mapped_state);
+            match visited.get(&mapped_state) {
+                Some((node_ix, seen_depth)) => {
+                    if let Some(limit) = self.depth_limit
+                        && depth < seen_depth
+                    {
+                        // Reached again by a shorter path, so the depth limit may
+                        // allow more of the graph to be explored from here than before
+                        visited.update(mapped_state.clone(), (node_ix, depth));
+                        let explored = seen_depth < limit;
+                        (Seen::Shallower { explored }, node_ix)
+                    } else {
+                        (Seen::Already, node_ix)
                     }
                 }
+                None => {
+                    let num_visited = self.num_visited.fetch_add(1, SeqCst);
+                    if let Some(max_states) = self.traversal.options.budget.max_states
+                        && num_visited >= max_states
+                    {
+                        self.stop(Incomplete::MaxStates);
+                        return Ok(vec![]);
+                    }
+                    self.max_depth_seen.fetch_max(depth, SeqCst);
+                    self.depth_stats(depth, |stats| stats.new_states += 1);
 
-                // Don't explore the same node twice
-                if already_seen {
-                    //     visitor(&state, VisitType::LoopTerminal)?;
-                    //     if record_terminals {
-                    //         loop_terminals.lock().insert(state);
-                    //     }
+                    let node_ix = if self.do_graphing {
+                        self.graph.lock().add_node(mapped_state.clone())
+                    } else {
+                        NodeIndex::end()
+                    };
+                    visited.insert_new(mapped_state.clone(), (node_ix, depth));
+                    (Seen::New, node_ix)
+                }
+            }
+        };
+
+        if self.do_graphing
+            && let Some((prev_node_ix, edge)) = prev_node
+            && let Some(edge) = map_action(&state, edge)
+        {
+            let ignore = *ignore_loopbacks && prev_node_ix == node_ix;
+            let key = (prev_node_ix, node_ix, edge);
+            if !ignore && self.visited_edges.lock(&key).insert(key.clone()) {
+                let (prev_node_ix, node_ix, edge) = key;
+                let _ = self.graph.lock().add_edge(prev_node_ix, node_ix, edge);
+            }
+        }
 
-                    continue;
+        match seen {
+            // Don't explore the same node twice
+            Seen::Already => return Ok(vec![]),
+            Seen::Shallower { .. } => {
+                // Record the state as reached by the shorter path
+                if self.do_graphing {
+                    self.graph.lock()[node_ix] = mapped_state.clone();
+                }
+                if machine.is_terminal(&state) {
+                    if self.record_terminals {
+                        self.terminals.lock().replace(mapped_state);
+                    }
+                    return Ok(vec![]);
+                }
+            }
+            Seen::New => {
+                for (i, (_, invariant)) in invariants.iter().enumerate() {
+                    if !invariant(&state) {
+                        self.violations.lock().push((i, node_ix));
+                    }
+                }
+                if let Some((target, limit)) = target
+                    && target(&state)
+                {
+                    let mut found = self.found.lock();
+                    found.push(node_ix);
+                    if found.len() >= *limit {
+                        self.stopped.store(true, SeqCst);
+                        return Ok(vec![]);
+                    }
                 }
 
                 // If this is a terminal state, no need to explore further.
-                // TODO: should also check if terminal due to no outgoing actions
                 if machine.is_terminal(&state) {
-                    num_terminations.fetch_add(1, SeqCst);
+                    self.num_terminations.fetch_add(1, SeqCst);
                     visitor(&state, VisitType::Terminal)?;
-                    if record_terminals {
-                        terminals.lock().insert(mapped_state);
+                    if self.record_terminals {
+                        self.terminals.lock().insert(mapped_state);
                     }
-                    continue;
+                    return Ok(vec![]);
                 } else {
                     visitor(&state, VisitType::Normal)?;
                 }
+            }
+        }
 
-                // Respect the depth limit
-                if depth >= max_depth.unwrap_or(usize::MAX) {
-                    continue;
-                }
+        // Respect the depth limit
+        if depth >= self.depth_limit.unwrap_or(usize::MAX) {
+            self.truncated.store(true, SeqCst);
+            return Ok(vec![]);
+        }
 
-                // Queue up visits to all nodes reachable from this node..
-                for action in all_actions.iter() {
-                    let prev_node = if do_graphing {
-                        Some((node_ix, action.clone()))
-                    } else {
-                        None
-                    };
-                    match machine.transition(state.clone(), action.clone()).map(first) {
-                        Ok(node) => {
-                            num_seen.fetch_add(1, SeqCst);
-                            seen.push((node, prev_node, depth + 1));
-                        }
-                        Err(err) => {
-                            num_edges_skipped.fetch_add(1, SeqCst);
+        // Queue up visits to all nodes reachable from this node..
+        let mut actions = self.traversal.actions_in(&state, self.all_actions);
+        if let Some(seed) = self.traversal.options.shuffle {
+            swarm::shuffle(actions.to_mut(), seed ^ node_ix.index() as u64);
+        }
+        let mut enabled = vec![];
+        let mut covered = Coverage::default();
+        for (i, action) in actions.iter().enumerate() {
+            match machine.transition(state.clone(), action.clone()).map(first) {
+                Ok(node) => {
+                    self.num_seen.fetch_add(1, SeqCst);
+                    if let Some(kind) = coverage {
+                        covered.record(kind(action), None);
+                    }
+                    enabled.push((i, node));
+                }
+                Err(err) => {
+                    self.num_edges_skipped.fetch_add(1, SeqCst);
+                    if let Some(kind) = coverage {
+                        covered.record(kind(action), Some(format!("{err:?}")));
+                    }
 
-                            if is_fatal_error(&err) {
-                                return Err(err);
-                            }
-                            if trace_errors {
-                                tracing::error!(?err, ?action, ?state, "edge skipped");
-                            }
-                        }
+                    if is_fatal_error(&err) {
+                        return Err(err);
+                    }
+                    if *trace_errors {
+                        tracing::error!(?err, ?action, ?state, "edge skipped");
                     }
                 }
             }
+        }
+        if coverage.is_some() {
+            self.coverage.lock().merge(covered);
+        }
+        self.depth_stats(depth, |stats| {
+            stats.expanded += 1;
+            stats.successors += enabled.len();
+        });
 
-            tracing::trace!("traversal thread {} done", thread_index);
-            let current_active_threads = active_threads.fetch_sub(1, SeqCst);
-
-            // handle the case where the first thread rips through all work
-            // before any other threads even get started
-            if thread_index == 0 && current_active_threads == 1 {
-                stop.store(true, SeqCst);
+        // Only now is it known whether the state is a dead end, which is reported
+        // with a further visit the first time the state is explored
+        if seen != (Seen::Shallower { explored: true }) {
+            if enabled.is_empty() {
+                self.deadlocks.lock().push(node_ix);
+                visitor(&state, VisitType::Deadlock)?;
+            } else if enabled.iter().all(|(_, next)| {
+                self.traversal.mapped(next.clone()).as_ref() == Some(&mapped_state)
+            }) {
+                visitor(&state, VisitType::LoopTerminal)?;
             }
-            Ok(())
         }
-    };
 
-    const THREADED: bool = true;
+        let next = self
+            .reduce(&state, &actions, enabled)
+            .into_iter()
+            .map(|(i, node)| {
+                // The edge is from the canonical relabeling of this state
+                let prev_node = if self.do_graphing {
+                    Some((
+                        node_ix,
+                        self.traversal.relabel_action(&actions[i], permutation),
+                    ))
+                } else {
+                    None
+                };
+                (node, prev_node, depth + 1)
+            })
+            .collect_vec();
+        self.num_queued.fetch_add(next.len(), SeqCst);
+        Ok(next)
+    }
 
-    if !THREADED {
-        task(0)?;
-    } else {
-        rayon::spawn_broadcast(move |broadcast_ctx: rayon::BroadcastContext<'_>| {
-            if let Err(err) = task(broadcast_ctx.index()) {
-                stop.store(true, SeqCst);
-                err_tx.send(err).unwrap();
-            }
-        });
+    /// Why the traversal should stop now, if it has gone over budget.
+    /// The memory used is only estimated every so often, since that takes a while.
+    fn over_budget(&self, iter: usize) -> Option<Incomplete> {
+        let budget = &self.traversal.options.budget;
+        if budget
+            .cancel
+            .as_ref()
+            .is_some_and(CancelHandle::is_cancelled)
+        {
+            return Some(Incomplete::Cancelled);
+        }
+        if budget
+            .time_limit
+            .is_some_and(|limit| self.start_time.elapsed() >= limit)
+        {
+            return Some(Incomplete::TimeLimit);
+        }
+        if let Some(limit) = budget.memory_limit
+            && iter.is_multiple_of(1024)
+            && self.memory_estimate() > limit
+        {
+            return Some(Incomplete::MemoryLimit);
+        }
+        None
+    }
+
+    /// Stop visiting states, because the traversal went over budget.
+    fn stop(&self, reason: Incomplete) {
+        self.incomplete.lock().get_or_insert(reason);
+        self.stopped.store(true, SeqCst);
+    }
+
+    /// Update the statistics of the states at a depth.
+    fn depth_stats(&self, depth: usize, update: impl FnOnce(&mut DepthStats)) {
+        let mut depths = self.depths.lock();
+        if depths.len() <= depth {
+            depths.resize(depth + 1, DepthStats::default());
+        }
+        update(&mut depths[depth]);
+    }
 
-        match err_rx.recv() {
-            Ok(err) => {
-                return Err(err);
+    /// Partial order reduction: given the actions tried in a state, and the index of each
+    /// enabled one along with the state it leads to, keep only those in the class of
+    /// mutually dependent actions with the fewest enabled, unless one of them leads to
+    /// a visited state.
+    fn reduce(
+        &self,
+        state: &M::State,
+        actions: &[M::Action],
+        enabled: Vec<(usize, M::State)>,
+    ) -> Vec<(usize, M::State)> {
+        let Some(independence) = &self.traversal.independence else {
+            return enabled;
+        };
+        if enabled.len() <= 1 {
+            return enabled;
+        }
+
+        // Grow the class of each enabled action through every action tried which it
+        // depends on, enabled or not, since an action outside the chosen class must not
+        // be able to affect it along any path. Actions unrelated to any enabled one
+        // are never compared with each other.
+        let mut class = vec![None; actions.len()];
+        let mut num_classes = 0;
+        for (start, _) in &enabled {
+            if class[*start].is_some() {
+                continue;
             }
-            Err(crossbeam::channel::RecvError) => {
-                // success
+            class[*start] = Some(num_classes);
+            let mut frontier = vec![*start];
+            while let Some(i) = frontier.pop() {
+                for j in 0..actions.len() {
+                    if class[j].is_none() && !independence(&actions[i], &actions[j], state) {
+                        class[j] = Some(num_classes);
+                        frontier.push(j);
+                    }
+                }
             }
+            num_classes += 1;
+        }
+
+        let counts = enabled.iter().map(|(i, _)| class[*i]).counts();
+        let (smallest, count) = counts
+            .into_iter()
+            .min_by_key(|(c, count)| (*count, *c))
+            .unwrap();
+        if count == enabled.len() {
+            return enabled;
+        }
+        let (ample, pruned): (Vec<_>, Vec<_>) = enabled
+            .into_iter()
+            .partition(|(i, _)| class[*i] == smallest);
+
+        // The cycle proviso: fully explore any state from which the reduced actions
+        // lead back to a visited state
+        let revisits = ample.iter().any(|(_, next)| {
+            self.traversal
+                .mapped(next.clone())
+                .is_some_and(|mapped| self.visited.lock(&mapped).get(&mapped).is_some())
+        });
+        if revisits {
+            let mut enabled = ample;
+            enabled.extend(pruned);
+            enabled.sort_by_key(|(i, _)| *i);
+            return enabled;
         }
+        self.num_edges_pruned.fetch_add(pruned.len(), SeqCst);
+        ample
     }
 
-    let report = TraversalReport {
-        time_taken: std::time::Instant::now().duration_since(start_time),
-        num_visited: visited_states.lock().len(),
-        num_terminations: num_terminations.load(SeqCst),
-        num_edges_skipped: num_edges_skipped.load(SeqCst),
-        total_steps: total_steps.load(SeqCst),
-        max_depth: max_depth_seen.load(SeqCst),
-    };
-    let terminals = record_terminals.then(|| {
-        Arc::into_inner(terminals).unwrap().into_inner()
-        // Arc::into_inner(loop_terminals).unwrap().into_inner(),
-    });
-    let graph = do_graphing.then_some(Arc::into_inner(graph).unwrap().into_inner());
-    Ok((report, graph, terminals))
+    /// Print a log message, if one is due at this iteration
+    fn trace(&self, iter: usize, depth: usize) {
+        if let Some((every, progress)) = &self.traversal.options.progress
+            && iter.is_multiple_of(*every)
+        {
+            progress(self.progress(iter, depth));
+        }
+
+        let trace_every = self.traversal.options.trace_every.unwrap_or(usize::MAX);
+        if iter == 0 || !iter.is_multiple_of(trace_every) {
+            return;
+        }
+        let trace = IterTrace {
+            iter,
+            queued: self.num_seen.load(SeqCst),
+            visited: self.visited.sum(Visited::len),
+            depth,
+        };
+        let mut prev = self.prev_trace.lock();
+
+        let queued_diff = trace.queued as isize - prev.queued as isize;
+        let visited_diff = trace.visited as isize - prev.visited as isize;
+
+        let queued_diff_str = if queued_diff == 0 {
+            "0".to_string().white()
+        } else if queued_diff > 0 {
+            format!("+{}", queued_diff.human_count_bare()).green()
+        } else {
+            format!("{}", queued_diff.human_count_bare()).red()
+        };
+
+        let visited_diff_str = if visited_diff == 0 {
+            "0".to_string().white()
+        } else if visited_diff > 0 {
+            format!("+{}", visited_diff.human_count_bare()).green()
+        } else {
+            format!("{}", visited_diff.human_count_bare()).red()
+        };
+
+        let depth_str = if trace.depth != prev.depth {
+            format!("depth={depth} ***").underline().bold()
+        } else {
+            format!("depth={depth}    ").white()
+        };
+
+        tracing::info!(
+            "iter={:<5} │ visited={:<8} Δ={:<8} │ queued={:<8} Δ={:<8} │ {}",
+            trace.iter.human_count_bare().to_string(),
+            trace.visited.human_count_bare().to_string(),
+            visited_diff_str,
+            trace.queued.human_count_bare().to_string(),
+            queued_diff_str,
+            depth_str,
+        );
+        *prev = trace;
+    }
+
+    /// A snapshot of the traversal's progress, at the given step and depth.
+    fn progress(&self, steps: usize, depth: usize) -> Progress {
+        let visited = self.visited.sum(Visited::len);
+        let elapsed = self.elapsed_before + self.start_time.elapsed();
+        Progress {
+            steps,
+            visited,
+            queued: self.num_queued.load(SeqCst),
+            depth,
+            max_depth: self.max_depth_seen.load(SeqCst),
+            elapsed,
+            states_per_second: visited as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            memory_estimate: self.memory_estimate(),
+        }
+    }
+
+    /// A rough estimate of the bytes of memory used by the visited states, the queue and
+    /// the graph.
+    fn memory_estimate(&self) -> usize {
+        let graph = if self.do_graphing {
+            let graph = self.graph.lock();
+            graph.node_count() * std::mem::size_of::<S>()
+                + graph.edge_count() * std::mem::size_of::<A>()
+        } else {
+            0
+        };
+        self.visited.sum(Visited::memory_estimate)
+            + self.num_queued.load(SeqCst) * std::mem::size_of::<Queued<M>>()
+            + graph
+    }
+}
+
+/// Take work from a thread's own queue, or else from the initial states,
+/// or else from another thread's queue.
+fn find_work<T>(local: &Worker<T>, global: &Injector<T>, stealers: &[Stealer<T>]) -> Option<T> {
+    local.pop().or_else(|| {
+        std::iter::repeat_with(|| {
+            global
+                .steal_batch_and_pop(local)
+                .or_else(|| stealers.iter().map(Stealer::steal).collect())
+        })
+        .find(|steal| !steal.is_retry())
+        .and_then(Steal::success)
+    })
+}
+
+/// Whether a state was visited before
+#[derive(PartialEq, Eq)]
+enum Seen {
+    New,
+    Already,
+    /// Visited before, but at a greater depth,
+    /// which may have been too deep to explore any further from
+    Shallower {
+        explored: bool,
+    },
 }
 
 /// A set of terminal nodes.
@@ -640,3 +2271,1002 @@ struct IterTrace {
     pub visited: usize,
     pub depth: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::store_path::{StorePathMachine, StorePathState};
+    use crate::prelude::*;
+
+    /// Counts up from zero by incrementing or doubling, never going past 10.
+    /// Reaching 6 is terminal.
+    #[derive(Clone, Debug)]
+    struct CountMachine;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash, Exhaustive)]
+    #[cfg_attr(feature = "recording", derive(serde::Serialize, serde::Deserialize))]
+    enum Count {
+        Inc,
+        Double,
+    }
+
+    impl Machine for CountMachine {
+        type State = u8;
+        type Action = Count;
+        type Error = String;
+        type Fx = ();
+
+        fn transition(&self, state: u8, action: Count) -> TransitionResult<Self> {
+            let next = match action {
+                Count::Inc => state + 1,
+                Count::Double => state * 2,
+            };
+            if next > 10 {
+                return Err(format!("{next} is too big"));
+            }
+            Ok((next, ()))
+        }
+
+        fn is_terminal(&self, state: &u8) -> bool {
+            *state == 6
+        }
+    }
+
+    /// Walks around a grid, one step right or up at a time.
+    #[derive(Clone, Debug)]
+    struct GridMachine(u16);
+
+    impl Machine for GridMachine {
+        type State = (u16, u16);
+        type Action = bool;
+        type Error = String;
+        type Fx = ();
+
+        fn transition(&self, (x, y): (u16, u16), right: bool) -> TransitionResult<Self> {
+            let next = if right { (x + 1, y) } else { (x, y + 1) };
+            if next.0 >= self.0 || next.1 >= self.0 {
+                return Err("off the grid".into());
+            }
+            Ok((next, ()))
+        }
+
+        fn is_terminal(&self, _: &(u16, u16)) -> bool {
+            false
+        }
+    }
+
+    /// Three nodes, each counting up to 2 independently.
+    /// Reaching 2 on every node is terminal.
+    #[derive(Clone, Debug)]
+    struct NodesMachine;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Exhaustive)]
+    enum Node {
+        A,
+        B,
+        C,
+    }
+
+    impl Machine for NodesMachine {
+        type State = [u8; 3];
+        type Action = Node;
+        type Error = String;
+        type Fx = ();
+
+        fn transition(&self, mut state: [u8; 3], node: Node) -> TransitionResult<Self> {
+            let count = &mut state[node as usize];
+            if *count == 2 {
+                return Err(format!("{node:?} is done"));
+            }
+            *count += 1;
+            Ok((state, ()))
+        }
+
+        fn is_terminal(&self, state: &[u8; 3]) -> bool {
+            *state == [2, 2, 2]
+        }
+    }
+
+    /// Jumps up by 1 to 3 at a time, never going past 20.
+    /// Its actions are not Exhaustive, so it lists the enabled ones in each state.
+    #[derive(Clone, Debug)]
+    struct JumpMachine;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Jump(u16);
+
+    impl Machine for JumpMachine {
+        type State = u16;
+        type Action = Jump;
+        type Error = String;
+        type Fx = ();
+
+        fn transition(&self, state: u16, Jump(n): Jump) -> TransitionResult<Self> {
+            if state + n > 20 {
+                return Err(format!("{} is too far", state + n));
+            }
+            Ok((state + n, ()))
+        }
+
+        fn enabled_actions(&self, state: &u16) -> Option<impl Iterator<Item = Jump>> {
+            let state = *state;
+            Some((1..=3).filter(move |n| state + n <= 20).map(Jump))
+        }
+    }
+
+    type Peer = UpTo<3>;
+
+    /// Three peers calling each other to share the secrets they know.
+    /// Everyone knowing every secret is terminal.
+    #[derive(Clone, Debug)]
+    struct GossipMachine;
+
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Relabel)]
+    struct Gossip(std::collections::BTreeMap<Peer, std::collections::BTreeSet<Peer>>);
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash, Exhaustive, Relabel)]
+    struct Call {
+        from: Peer,
+        to: Peer,
+    }
+
+    impl Gossip {
+        fn initial() -> Self {
+            Self(Peer::all_values().map(|p| (p, [p].into())).into())
+        }
+    }
+
+    impl Machine for GossipMachine {
+        type State = Gossip;
+        type Action = Call;
+        type Error = String;
+        type Fx = ();
+
+        fn transition(&self, mut state: Gossip, call: Call) -> TransitionResult<Self> {
+            if call.from == call.to {
+                return Err("can't call yourself".into());
+            }
+            let mut secrets = state.0[&call.from].clone();
+            secrets.extend(state.0[&call.to].iter().copied());
+            if secrets == state.0[&call.from] && secrets == state.0[&call.to] {
+                return Err("nothing to share".into());
+            }
+            state.0.insert(call.from, secrets.clone());
+            state.0.insert(call.to, secrets);
+            Ok((state, ()))
+        }
+
+        fn is_terminal(&self, state: &Gossip) -> bool {
+            state.0.values().all(|secrets| secrets.len() == 3)
+        }
+    }
+
+    const STRATEGIES: [Strategy; 4] = [
+        Strategy::Bfs,
+        Strategy::Dfs,
+        Strategy::IterativeDeepening,
+        Strategy::Parallel,
+    ];
+
+    #[allow(clippy::type_complexity)]
+    fn graph_sets<S, A>(graph: &DiGraph<S, A>) -> (HashSet<S>, HashSet<(S, S, A)>)
+    where
+        S: Clone + Eq + Hash,
+        A: Clone + Eq + Hash,
+    {
+        let nodes = graph.node_weights().cloned().collect();
+        let edges = graph
+            .edge_indices()
+            .map(|e| {
+                let (a, b) = graph.edge_endpoints(e).unwrap();
+                (graph[a].clone(), graph[b].clone(), graph[e].clone())
+            })
+            .collect();
+        (nodes, edges)
+    }
+
+    #[test]
+    fn strategies_explore_the_same_graph() {
+        for max_depth in [None, Some(4)] {
+            let graphs = STRATEGIES.map(|strategy| {
+                let mut traversal = CountMachine.traverse([0]).strategy(strategy);
+                if let Some(max_depth) = max_depth {
+                    traversal = traversal.max_depth(max_depth);
+                }
+                graph_sets(&traversal.diagram().unwrap())
+            });
+            // Depth 4 reaches 5 and 8 only from the 4 reached by doubling 2,
+            // which depth-first search finds after reaching 4 by a longer path
+            let expected_nodes = match max_depth {
+                None => (0..=10).filter(|n| *n != 7).collect::<HashSet<_>>(),
+                Some(_) => HashSet::from([0, 1, 2, 3, 4, 5, 6, 8]),
+            };
+            assert_eq!(graphs[0].0, expected_nodes);
+            assert_eq!(graphs[0], graphs[1]);
+            assert_eq!(graphs[0], graphs[2]);
+            assert_eq!(graphs[0], graphs[3]);
+        }
+    }
+
+    #[test]
+    fn thread_counts() {
+        let graph = |strategy, threads| {
+            let graph = GridMachine(40)
+                .traverse([(0, 0)])
+                .strategy(strategy)
+                .threads(threads)
+                .diagram()
+                .unwrap();
+            graph_sets(&graph)
+        };
+        let expected = graph(Strategy::Dfs, 1);
+        assert_eq!(expected.0.len(), 40 * 40);
+        for strategy in [Strategy::Bfs, Strategy::Parallel] {
+            for threads in [1, 2, 8] {
+                assert_eq!(graph(strategy, threads), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn deterministic_graphs() {
+        for strategy in STRATEGIES {
+            let graph = || {
+                let graph = CountMachine
+                    .traverse([0])
+                    .strategy(strategy)
+                    .deterministic(true)
+                    .diagram()
+                    .unwrap();
+                let nodes = graph.node_weights().copied().collect_vec();
+                let edges = graph
+                    .raw_edges()
+                    .iter()
+                    .map(|e| (e.source(), e.target(), e.weight.clone()))
+                    .collect_vec();
+                (nodes, edges)
+            };
+            let first = graph();
+            for _ in 0..10 {
+                assert_eq!(graph(), first);
+            }
+        }
+    }
+
+    #[test]
+    fn shortest_paths() {
+        let strategies = [Strategy::Bfs, Strategy::Dfs, Strategy::IterativeDeepening];
+        let path_lengths = strategies.map(|strategy| {
+            let terminals = StorePathMachine::from(CountMachine)
+                .traverse([StorePathState::new(0)])
+                .strategy(strategy)
+                .run_terminal()
+                .unwrap();
+            let terminal = terminals.into_iter().exactly_one().unwrap();
+            assert_eq!(terminal.state, 6);
+            terminal.path.len()
+        });
+        // Depth-first search follows increments first: 0, 1, ..., 6
+        assert_eq!(path_lengths, [4, 6, 4]);
+    }
+
+    #[cfg(feature = "recording")]
+    #[test]
+    fn spilling_explores_the_same_graph() {
+        let expected = graph_sets(&GridMachine(30).traverse([(0, 0)]).diagram().unwrap());
+        for strategy in STRATEGIES {
+            let graph = GridMachine(30)
+                .traverse([(0, 0)])
+                .strategy(strategy)
+                .spill_to_disk(std::env::temp_dir(), 20)
+                .unwrap()
+                .diagram()
+                .unwrap();
+            assert_eq!(graph_sets(&graph), expected);
+        }
+        let terminals = CountMachine
+            .traverse([0])
+            .spill_to_disk(std::env::temp_dir(), 2)
+            .unwrap()
+            .run_terminal()
+            .unwrap();
+        assert_eq!(terminals, HashSet::from([6]));
+
+        // A directory which can't be created is found before the traversal starts
+        let file = std::env::temp_dir().join(format!("polestar-file-{}", std::process::id()));
+        std::fs::write(&file, "not a directory").unwrap();
+        let spilled = CountMachine
+            .traverse([0])
+            .spill_to_disk(file.join("spill"), 2);
+        assert!(spilled.is_err());
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[cfg(feature = "recording")]
+    #[test]
+    fn checkpoint_and_resume() {
+        let path = std::env::temp_dir().join(format!("polestar-test-{}", std::process::id()));
+        let traversal = || {
+            let visits = Arc::new(AtomicUsize::new(0));
+            let counter = visits.clone();
+            let traversal = GridMachine(30)
+                .traverse([(0, 0)])
+                .is_fatal_error(|e| e == "crash")
+                .visitor(move |_, _| {
+                    counter.fetch_add(1, SeqCst);
+                    Ok(())
+                });
+            (traversal, visits)
+        };
+        let (full, _) = traversal();
+        let expected = graph_sets(&full.diagram().unwrap());
+
+        // Crash while visiting the states 20 steps away from the start
+        let (crashing, _) = traversal();
+        let err = crashing
+            .visitor(|&(x, y), _| match x + y {
+                20 => Err("crash".to_string()),
+                _ => Ok(()),
+            })
+            .checkpoint(&path, std::time::Duration::ZERO)
+            .unwrap()
+            .diagram()
+            .unwrap_err();
+        assert_eq!(err, "crash");
+
+        let (resumed, visits) = traversal();
+        let graph = resumed.resume_from(&path).unwrap().diagram().unwrap();
+        assert_eq!(graph_sets(&graph), expected);
+        // The 210 states less than 20 steps away were not visited again,
+        // and the far corner is visited once more when it's found to be stuck
+        assert_eq!(visits.load(SeqCst), 30 * 30 - 210 + 1);
+
+        // A checkpoint cut short can't be resumed from
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        let (resumed, _) = traversal();
+        let err = resumed.resume_from(&path).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        std::fs::write(&path, [b'x'; 100]).unwrap();
+        let (resumed, _) = traversal();
+        let err = resumed.resume_from(&path).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+        let (resumed, _) = traversal();
+        let err = resumed.resume_from(&path).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+        // Only breadth-first traversals of exact states support checkpoints
+        let (dfs, _) = traversal();
+        let err = dfs
+            .strategy(Strategy::Dfs)
+            .checkpoint(&path, std::time::Duration::ZERO)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn partial_order_reduction() {
+        let visits = |strategy, independence: fn(&Node, &Node, &[u8; 3]) -> bool| {
+            let visited = Arc::new(Mutex::new(HashSet::new()));
+            let recorder = visited.clone();
+            let terminals = NodesMachine
+                .traverse([[0, 0, 0]])
+                .strategy(strategy)
+                .independence(independence)
+                .visitor(move |s, _| {
+                    recorder.lock().insert(*s);
+                    Ok(())
+                })
+                .run_terminal()
+                .unwrap();
+            assert_eq!(terminals, HashSet::from([[2, 2, 2]]));
+            Arc::into_inner(visited).unwrap().into_inner().len()
+        };
+        for strategy in STRATEGIES {
+            // Nothing is independent
+            assert_eq!(visits(strategy, |_, _, _| false), 27);
+            // Every node is independent, so a single interleaving is explored
+            assert_eq!(visits(strategy, |a, b, _| a != b), 7);
+            // Only C is independent of the others
+            let c = |a: &Node, b: &Node, _: &_| a != b && (*a == Node::C || *b == Node::C);
+            assert!(visits(strategy, c) < 27);
+        }
+    }
+
+    #[test]
+    fn invariants() {
+        for strategy in STRATEGIES {
+            let violations = GridMachine(10)
+                .traverse([(0, 0)])
+                .strategy(strategy)
+                .invariant("near the origin", |(x, y)| x + y < 5)
+                .invariant("on the grid", |(x, y)| *x < 10 && *y < 10)
+                .invariant("not at (3, 4)", |s| *s != (3, 4))
+                .check_invariants()
+                .unwrap();
+            let names = violations.iter().map(|v| v.name.as_str()).collect_vec();
+            assert_eq!(names, ["near the origin", "not at (3, 4)"]);
+
+            assert_eq!(violations[0].path.len(), 5);
+            assert_eq!(violations[1].path.len(), 7);
+            assert_eq!(*violations[1].state(), (3, 4));
+            for violation in violations {
+                // The path is a real one
+                let mut state = violation.initial;
+                for (action, next) in violation.path {
+                    state = GridMachine(10).transition(state, action).unwrap().0;
+                    assert_eq!(state, next);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deadlocks() {
+        for strategy in STRATEGIES {
+            let traversal = || GridMachine(10).traverse([(0, 0)]).strategy(strategy);
+
+            // Deadlocks are always counted, but only reported if checked for
+            let traversed = traverse(traversal(), false, false).unwrap();
+            assert_eq!(traversed.report.num_deadlocks, 1);
+            assert_eq!(traversal().check_invariants().unwrap(), vec![]);
+
+            let violations = traversal().check_deadlock(true).check_invariants().unwrap();
+            assert_eq!(violations.len(), 1);
+            assert_eq!(violations[0].name, "deadlock");
+            assert_eq!(*violations[0].state(), (9, 9));
+            assert_eq!(violations[0].path.len(), 18);
+
+            // Only 10 is stuck, since 6 is terminal, and it is visited as usual first
+            let visits = Arc::new(Mutex::new(vec![]));
+            let recorder = visits.clone();
+            CountMachine
+                .traverse([0])
+                .strategy(strategy)
+                .visitor(move |s, visit| {
+                    recorder.lock().push((*s, visit));
+                    Ok(())
+                })
+                .run_terminal()
+                .unwrap();
+            let visits = Arc::into_inner(visits).unwrap().into_inner();
+            let unusual: HashMap<_, _> = visits
+                .iter()
+                .copied()
+                .filter(|(_, visit)| *visit != VisitType::Normal)
+                .collect();
+            assert_eq!(
+                unusual,
+                [(6, VisitType::Terminal), (10, VisitType::Deadlock)].into()
+            );
+            // Iterative deepening visits it again in each round, but only finds it stuck
+            // once it is shallow enough to explore
+            let stuck = visits
+                .iter()
+                .filter(|(s, _)| *s == 10)
+                .map(|(_, visit)| *visit)
+                .collect_vec();
+            assert!(stuck.ends_with(&[VisitType::Normal, VisitType::Deadlock]));
+
+            // Past 3, every step leads back to the same mapped state
+            let visits = Arc::new(Mutex::new(HashMap::new()));
+            let recorder = visits.clone();
+            GridMachine(10)
+                .traverse([(0, 0)])
+                .strategy(strategy)
+                .map_state(|(x, _)| Some((x.min(3), 0)))
+                .visitor(move |s, visit| {
+                    recorder.lock().insert(*s, visit);
+                    Ok(())
+                })
+                .run_terminal()
+                .unwrap();
+            let visits = Arc::into_inner(visits).unwrap().into_inner();
+            assert_eq!(
+                visits,
+                [
+                    ((0, 0), VisitType::Normal),
+                    ((1, 0), VisitType::Normal),
+                    ((2, 0), VisitType::Normal),
+                    ((3, 0), VisitType::LoopTerminal),
+                ]
+                .into()
+            );
+        }
+    }
+
+    #[test]
+    fn find() {
+        for strategy in STRATEGIES {
+            let grid = || GridMachine(10).traverse([(0, 0)]).strategy(strategy);
+
+            let witness = grid().find(|s| *s == (3, 4)).unwrap().unwrap();
+            assert_eq!(*witness.state(), (3, 4));
+            assert_eq!(witness.states().count(), 8);
+            let end = GridMachine(10)
+                .apply_actions_((0, 0), witness.actions().copied())
+                .unwrap();
+            assert_eq!(end, (3, 4));
+
+            assert_eq!(grid().find(|(x, _)| *x >= 10).unwrap(), None);
+
+            let found = grid().find_all(|(x, y)| x + y == 4, 3).unwrap();
+            assert!(found.len() >= 3);
+            assert!(found.iter().all(|w| w.path.len() == 4));
+            let all = grid().find_all(|(x, y)| x + y == 4, usize::MAX).unwrap();
+            assert_eq!(all.len(), 5);
+        }
+
+        // The search stops early
+        let visits = Arc::new(AtomicUsize::new(0));
+        let counter = visits.clone();
+        GridMachine(10)
+            .traverse([(0, 0)])
+            .visitor(move |_, _| {
+                counter.fetch_add(1, SeqCst);
+                Ok(())
+            })
+            .find(|s| *s == (1, 1))
+            .unwrap()
+            .unwrap();
+        assert!(visits.load(SeqCst) < 10);
+
+        // Any two peers knowing every secret will do
+        let everything = |g: &Gossip| g.0.values().filter(|s| s.len() == 3).count() >= 2;
+        let witness = GossipMachine
+            .traverse([Gossip::initial()])
+            .symmetry::<Peer>()
+            .find(everything)
+            .unwrap()
+            .unwrap();
+        assert_eq!(witness.path.len(), 2);
+        assert!(everything(witness.state()));
+    }
+
+    #[test]
+    fn coverage() {
+        // Iterative deepening explores some states more than once
+        for strategy in [Strategy::Bfs, Strategy::Dfs, Strategy::Parallel] {
+            let report = CountMachine
+                .traverse([0])
+                .strategy(strategy)
+                .coverage(|a| format!("{a:?}"))
+                .run()
+                .unwrap();
+            let coverage = report.coverage.unwrap();
+            let inc = &coverage.actions["Inc"];
+            assert_eq!((inc.successes, inc.failures), (8, 1));
+            assert_eq!(inc.errors, [("\"11 is too big\"".to_string(), 1)].into());
+            let double = &coverage.actions["Double"];
+            assert_eq!((double.successes, double.failures), (6, 3));
+            assert_eq!(double.errors.len(), 3);
+            assert_eq!(coverage.never_succeeded().count(), 0);
+        }
+
+        // Nowhere to go on a grid with a single cell
+        let report = GridMachine(1)
+            .traverse([(0, 0)])
+            .coverage(|right| if *right { "right" } else { "up" }.to_string())
+            .run()
+            .unwrap();
+        let coverage = report.coverage.unwrap();
+        assert_eq!(coverage.never_succeeded().collect_vec(), ["right", "up"]);
+        assert_eq!(coverage.actions["up"].errors["\"off the grid\""], 1);
+
+        assert!(CountMachine.traverse([0]).run().unwrap().coverage.is_none());
+    }
+
+    #[test]
+    fn progress() {
+        for strategy in STRATEGIES {
+            let snapshots = Arc::new(Mutex::new(vec![]));
+            let recorder = snapshots.clone();
+            let report = GridMachine(10)
+                .traverse([(0, 0)])
+                .strategy(strategy)
+                .deterministic(true)
+                .progress(10, move |progress| recorder.lock().push(progress))
+                .run()
+                .unwrap();
+            let snapshots = Arc::into_inner(snapshots).unwrap().into_inner();
+            assert!(snapshots.last().unwrap().memory_estimate > 0);
+            // Iterative deepening starts counting again with each depth limit
+            if strategy != Strategy::IterativeDeepening {
+                assert_eq!(snapshots.len(), report.total_steps.div_ceil(10));
+                for (a, b) in snapshots.iter().tuple_windows() {
+                    assert!(a.steps < b.steps);
+                    assert!(a.visited <= b.visited);
+                }
+            }
+
+            // The grid widens until its diagonal, then narrows
+            let new_states = report.depths.iter().map(|d| d.new_states).collect_vec();
+            let diagonals = (0..19).map(|d| 10 - (d as usize).abs_diff(9)).collect_vec();
+            assert_eq!(new_states, diagonals);
+            assert_eq!(report.depths[0].branching_factor(), 2.0);
+            assert_eq!(report.depths[18].branching_factor(), 0.0);
+            // Every state has a step right unless on the right edge, and likewise up
+            assert_eq!(report.branching_factor(), (9.0 * 10.0 * 2.0) / 100.0);
+        }
+    }
+
+    #[test]
+    fn budgets() {
+        for strategy in STRATEGIES {
+            let grid = |size| GridMachine(size).traverse([(0, 0)]).strategy(strategy);
+
+            let report = grid(10).run().unwrap();
+            assert_eq!(report.incomplete, None);
+
+            let report = grid(10).max_states(20).run().unwrap();
+            assert_eq!(report.incomplete, Some(Incomplete::MaxStates));
+            assert_eq!(report.num_visited, 20);
+            let graph = grid(10).max_states(20).diagram().unwrap();
+            assert_eq!(graph.node_count(), 20);
+
+            let report = grid(10)
+                .time_limit(std::time::Duration::ZERO)
+                .run()
+                .unwrap();
+            assert_eq!(report.incomplete, Some(Incomplete::TimeLimit));
+            assert_eq!(report.num_visited, 0);
+
+            let report = grid(40).memory_limit(1).run().unwrap();
+            assert_eq!(report.incomplete, Some(Incomplete::MemoryLimit));
+            assert!(report.num_visited < 40 * 40);
+
+            let cancel = CancelHandle::new();
+            let handle = cancel.clone();
+            let report = grid(10)
+                .cancel_handle(cancel.clone())
+                .visitor(move |s, _| {
+                    if *s == (5, 5) {
+                        handle.cancel();
+                    }
+                    Ok(())
+                })
+                .run()
+                .unwrap();
+            assert!(cancel.is_cancelled());
+            assert_eq!(report.incomplete, Some(Incomplete::Cancelled));
+            assert!(report.num_visited < 100);
+        }
+    }
+
+    #[test]
+    fn simulate() {
+        let grid = || {
+            GridMachine(10)
+                .traverse([(0, 0)])
+                .invariant("not at (3, 4)", |s| *s != (3, 4))
+        };
+        let simulation = grid().simulate(50, 30, 7);
+        assert_eq!(simulation, grid().simulate(50, 30, 7));
+        assert_eq!(simulation.num_walks, 50);
+        assert!(!simulation.failures.is_empty());
+        for failed in &simulation.failures {
+            assert_eq!(
+                failed.failure,
+                WalkFailure::Invariant("not at (3, 4)".into())
+            );
+            let actions = failed.path.iter().map(|(a, _)| *a);
+            let end = GridMachine(10).apply_actions_(failed.initial, actions);
+            assert_eq!(end.unwrap(), (3, 4));
+        }
+
+        // Walks spread out over the grid, even when generating arbitrary actions
+        let simulation = grid().arbitrary_actions().simulate(50, 30, 7);
+        assert!(simulation.num_visited > 50);
+
+        // Every walk long enough ends up stuck in the far corner
+        let simulation = GridMachine(10)
+            .traverse([(0, 0)])
+            .check_deadlock(true)
+            .simulate(20, 30, 1);
+        assert_eq!(simulation.failures.len(), 20);
+        for failed in simulation.failures {
+            assert_eq!(failed.failure, WalkFailure::Deadlock);
+            assert_eq!(*failed.state(), (9, 9));
+        }
+
+        let simulation = CountMachine
+            .traverse([0])
+            .visitor(|s, _| if *s == 9 { Err("nine".into()) } else { Ok(()) })
+            .simulate(100, 10, 3);
+        assert!(simulation.failures.iter().all(|f| *f.state() == 9));
+        assert_eq!(
+            simulation.failures[0].failure,
+            WalkFailure::Error("nine".into())
+        );
+    }
+
+    #[test]
+    fn swarm() {
+        let grid = || {
+            GridMachine(30)
+                .traverse([(0, 0)])
+                .max_states(50)
+                .coverage(|right| if *right { "right" } else { "up" }.to_string())
+        };
+        let single = grid().strategy(Strategy::Dfs).run().unwrap();
+        assert_eq!(single.num_visited, 50);
+
+        // Members going different ways cover more between them than any one of them
+        let swarm = grid().swarm(8, 3).unwrap();
+        assert_eq!(swarm.searches.len(), 4);
+        assert_eq!(swarm.num_walks, 4);
+        assert!(swarm.num_visited > 50);
+        assert_eq!(swarm.num_visited, grid().swarm(8, 3).unwrap().num_visited);
+        assert!(swarm.total_steps > swarm.searches.iter().map(|m| m.total_steps).sum::<usize>());
+        let coverage = swarm.coverage.unwrap();
+        let successes = |kind: &str| {
+            swarm
+                .searches
+                .iter()
+                .map(|m| m.coverage.as_ref().unwrap().actions[kind].successes)
+                .sum::<usize>()
+        };
+        assert_eq!(coverage.actions["right"].successes, successes("right"));
+        assert_eq!(coverage.actions["up"].successes, successes("up"));
+
+        // Each violation is reported once, with the shortest path any member found
+        let swarm = GridMachine(10)
+            .traverse([(0, 0)])
+            .max_depth(12)
+            .invariant("not at (2, 3)", |s| *s != (2, 3))
+            .invariant("not at (9, 9)", |s| *s != (9, 9))
+            .invariant("not at (1, 1)", |s| *s != (1, 1))
+            .swarm(6, 0)
+            .unwrap();
+        let names = swarm
+            .violations
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect_vec();
+        assert_eq!(names, ["not at (2, 3)", "not at (1, 1)"]);
+        assert_eq!(swarm.violations[0].path.len(), 5);
+        assert_eq!(*swarm.violations[0].state(), (2, 3));
+        assert!(swarm
+            .searches
+            .iter()
+            .all(|m| (6..=12).contains(&m.max_depth)));
+
+        // Without a limit on states, each member still stops at a default budget
+        let swarm = GridMachine(1000).traverse([(0, 0)]).swarm(2, 0).unwrap();
+        assert_eq!(swarm.searches[0].num_visited, 100_000);
+        assert_eq!(swarm.searches[0].incomplete, Some(Incomplete::MaxStates));
+        assert!(swarm.num_visited > 100_000);
+    }
+
+    #[test]
+    fn refinement() {
+        // Going right on the grid is counting along its bottom row, going up is stuttering
+        let bottom_row = |(x, _): &(u16, u16)| (*x, 0);
+        let right_only = |right: &bool| if *right { vec![true] } else { vec![] };
+        let violation = GridMachine(10)
+            .traverse([(0, 0)])
+            .check_refinement(&GridMachine(10), bottom_row, right_only)
+            .unwrap();
+        assert_eq!(violation, None);
+
+        // A narrower grid can't follow the steps off its edge
+        let violation = GridMachine(10)
+            .traverse([(0, 0)])
+            .check_refinement(&GridMachine(4), bottom_row, right_only)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            violation.path,
+            (1..=4).map(|x| (true, (x, 0))).collect_vec()
+        );
+        assert_eq!(violation.abstract_state, (3, 0));
+        assert_eq!(
+            violation.failure,
+            RefinementFailure::Error {
+                error: "off the grid".to_string(),
+                state: (3, 0),
+                action: true,
+            }
+        );
+
+        // Stuttering steps must leave the abstract state alone
+        let violation = GridMachine(10)
+            .traverse([(0, 0)])
+            .check_refinement(&GridMachine(10), |s| *s, right_only)
+            .unwrap()
+            .unwrap();
+        assert_eq!(violation.path, vec![(false, (0, 1))]);
+        assert_eq!(
+            violation.failure,
+            RefinementFailure::Mismatch {
+                expected: (0, 1),
+                actual: (0, 0),
+            }
+        );
+    }
+
+    #[test]
+    fn symmetry() {
+        let full = GossipMachine
+            .traverse([Gossip::initial()])
+            .diagram()
+            .unwrap();
+        let symmetry = Symmetry::<Peer>::full();
+        let canonical: HashSet<_> = full.node_weights().map(|s| symmetry.canonical(s)).collect();
+        assert!(canonical.len() < full.node_count());
+
+        for strategy in STRATEGIES {
+            let reduced = GossipMachine
+                .traverse([Gossip::initial()])
+                .strategy(strategy)
+                .symmetry::<Peer>()
+                .diagram()
+                .unwrap();
+            let (nodes, _) = graph_sets(&reduced);
+            assert_eq!(nodes, canonical);
+
+            // Each edge is an action which can be taken in the state it comes from
+            for edge in reduced.edge_references() {
+                let (next, _) = GossipMachine
+                    .transition(reduced[edge.source()].clone(), edge.weight().clone())
+                    .unwrap();
+                assert_eq!(symmetry.canonical(&next), reduced[edge.target()]);
+            }
+        }
+
+        // A mapping set afterwards is applied to the canonical states
+        let reduced = GossipMachine
+            .traverse([Gossip::initial()])
+            .symmetry::<Peer>()
+            .map_state(Some)
+            .diagram()
+            .unwrap();
+        assert_eq!(graph_sets(&reduced).0, canonical);
+    }
+
+    #[test]
+    fn compaction() {
+        let visits = |compaction, strategy| {
+            let visited = Arc::new(Mutex::new(HashSet::new()));
+            let recorder = visited.clone();
+            GridMachine(40)
+                .traverse([(0, 0)])
+                .strategy(strategy)
+                .compaction(compaction)
+                .unwrap()
+                .visitor(move |s, _| {
+                    recorder.lock().insert(*s);
+                    Ok(())
+                })
+                .run_terminal()
+                .unwrap();
+            Arc::into_inner(visited).unwrap().into_inner().len()
+        };
+        let compactions = [
+            Compaction::HashCompaction,
+            Compaction::Bitstate {
+                log2_bits: 24,
+                hashes: 3,
+            },
+        ];
+        for compaction in compactions {
+            for strategy in STRATEGIES {
+                assert_eq!(visits(compaction, strategy), 40 * 40);
+            }
+        }
+
+        // A tiny bit array fills up and misses states
+        let tiny = Compaction::Bitstate {
+            log2_bits: 6,
+            hashes: 2,
+        };
+        assert!(visits(tiny, Strategy::Dfs) < 40 * 40);
+        // Even with fewer bits than shards, each shard has some bits to set
+        let tinier = Compaction::Bitstate {
+            log2_bits: 0,
+            hashes: 2,
+        };
+        assert!(visits(tinier, Strategy::Parallel) < 40 * 40);
+    }
+
+    #[test]
+    fn bitstate_too_large() {
+        for log2_bits in [60, usize::BITS] {
+            let compacted = GridMachine(4)
+                .traverse([(0, 0)])
+                .compaction(Compaction::Bitstate {
+                    log2_bits,
+                    hashes: 2,
+                });
+            assert!(compacted.is_err());
+        }
+    }
+
+    #[test]
+    fn cost_guided_search() {
+        let grid = || GridMachine(10).traverse([(0, 0)]);
+        let far = |(x, y): &(u16, u16)| x + y == 6;
+
+        // Going up costs more, so the cheapest way to go far is to go right
+        let cost = |_: &_, right: &bool, _: &_| if *right { 1 } else { 2 };
+        let dijkstra = grid().search_min_cost(cost, far, |_| 0).unwrap();
+        let a_star = grid()
+            .search_min_cost(cost, far, |(x, y)| 6 - x - y)
+            .unwrap();
+        assert_eq!(dijkstra, a_star);
+        assert_eq!(dijkstra.len(), 1);
+        assert_eq!(dijkstra[0].cost, 6);
+        assert_eq!(*dijkstra[0].goal(), (6, 0));
+
+        // Every far state is equally cheap to reach
+        let paths = grid().search_min_cost(|_, _, _| 1, far, |_| 0).unwrap();
+        assert_eq!(paths.len(), 7);
+        for path in paths {
+            assert_eq!(path.cost, 6);
+            assert_eq!(path.path.len(), 6);
+            let actions = path.path.iter().map(|(a, _)| *a);
+            let goal = GridMachine(10).apply_actions_((0, 0), actions).unwrap();
+            assert_eq!(goal, *path.goal());
+        }
+
+        // Nothing is far enough within the depth limit
+        let paths = grid().max_depth(5).search_min_cost(cost, far, |_| 0);
+        assert_eq!(paths.unwrap(), vec![]);
+
+        // Going right takes less time but more money, and each mix of the two is optimal
+        let cost = |_: &_, right: &bool, _: &_| if *right { (1, 3) } else { (3, 1) };
+        let near = |(x, y): &(u16, u16)| x + y == 4;
+        let cheapest = grid().search_min_cost(cost, near, |_| (0, 0)).unwrap();
+        assert_eq!(cheapest.len(), 1);
+        assert_eq!(cheapest[0].cost, (4, 12));
+        let frontier = grid()
+            .search_pareto_frontier(cost, near, |(x, y)| (4 - x - y, 4 - x - y))
+            .unwrap();
+        let costs = frontier.iter().map(|p| p.cost).collect_vec();
+        assert_eq!(costs, [(4, 12), (6, 10), (8, 8), (10, 6), (12, 4)]);
+    }
+
+    #[test]
+    fn enabled_actions() {
+        for strategy in STRATEGIES {
+            let traversed = traverse(
+                Traversal::with_enabled_actions(JumpMachine, [0], Vec::new).strategy(strategy),
+                true,
+                false,
+            )
+            .unwrap();
+            assert_eq!(traversed.report.num_visited, 21);
+            assert_eq!(traversed.report.num_edges_skipped, 0);
+            assert_eq!(traversed.graph.unwrap().edge_count(), 18 * 3 + 2 + 1);
+        }
+    }
+
+    #[test]
+    fn unlisted_actions_fall_back_to_all_actions() {
+        // CountMachine lists no actions, so only those given as all the actions are tried
+        let nothing = Traversal::with_enabled_actions(CountMachine, [0], Vec::new);
+        assert_eq!(nothing.diagram().unwrap().node_count(), 1);
+        let only_inc = Traversal::with_enabled_actions(CountMachine, [0], || vec![Count::Inc])
+            .coverage(|a| format!("{a:?}"));
+        let traversed = traverse(only_inc, true, false).unwrap();
+        // Counting up stops at the terminal state 6
+        assert_eq!(traversed.report.num_visited, 7);
+        let coverage = traversed.report.coverage.unwrap();
+        assert_eq!(coverage.actions.keys().collect_vec(), ["Inc"]);
+    }
+
+    #[test]
+    fn fatal_errors_stop_every_strategy() {
+        for strategy in STRATEGIES {
+            let err = CountMachine
+                .traverse([0])
+                .strategy(strategy)
+                .is_fatal_error(|_| true)
+                .diagram()
+                .unwrap_err();
+            assert!(err.ends_with("is too big"));
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Limits on the resources a traversal may use, and cancelling a traversal in progress.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// The limits a traversal stops at, leaving it incomplete.
+#[derive(Clone, Default)]
+pub(crate) struct Budget {
+    pub max_states: Option<usize>,
+    pub time_limit: Option<Duration>,
+    pub memory_limit: Option<usize>,
+    pub cancel: Option<CancelHandle>,
+}
+
+/// Why a traversal stopped before exploring every reachable state
+/// (see [`TraversalReport::incomplete`](super::TraversalReport::incomplete)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Incomplete {
+    /// As many distinct states as allowed by
+    /// [`Traversal::max_states`](super::Traversal::max_states) were visited.
+    MaxStates,
+    /// The traversal ran for longer than allowed by
+    /// [`Traversal::time_limit`](super::Traversal::time_limit).
+    TimeLimit,
+    /// The estimated memory used went over
+    /// [`Traversal::memory_limit`](super::Traversal::memory_limit).
+    MemoryLimit,
+    /// The traversal was cancelled through its [`CancelHandle`].
+    Cancelled,
+}
+
+/// A handle through which a traversal can be cancelled from another thread
+/// (see [`Traversal::cancel_handle`](super::Traversal::cancel_handle)).
+/// Clones of a handle all cancel the same traversals.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    /// A handle which has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop every traversal using this handle, as soon as each finishes visiting the states
+    /// it is visiting.
+    pub fn cancel(&self) {
+        self.0.store(true, SeqCst);
+    }
+
+    /// Whether [`CancelHandle::cancel`] has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(SeqCst)
+    }
+}
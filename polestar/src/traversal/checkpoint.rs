@@ -0,0 +1,444 @@
+//! Checkpoints of a traversal in progress, from which it can be resumed.
+
+use std::{
+    fs::{self, File},
+    hash::Hash,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::Ordering::SeqCst,
+    time::Duration,
+};
+
+use petgraph::graph::NodeIndex;
+
+use exhaustive::Exhaustive;
+use petgraph::graph::DiGraph;
+
+use super::storage::{bytes, number, write_bytes, Codec, Visited};
+use super::{
+    ActionCoverage, Coverage, DepthStats, Explorer, InvariantViolation, Queued, TerminalSet,
+    Traversal, TraversalReport, Witness,
+};
+use crate::machine::Cog;
+use crate::model_checker::{ModelChecker, ModelCheckerError, ModelCheckerState};
+use crate::Machine;
+
+const MAGIC: &[u8] = b"polestar checkpoint 1\n";
+
+/// A traversal which writes checkpoints, or resumes from one
+/// (see [`Traversal::checkpoint`] and [`Traversal::resume_from`]).
+///
+/// Checkpoints are only supported by some strategies and compactions, without spilling,
+/// so none of those can be changed any more, and the traversal can only be run,
+/// in any of the ways a [`Traversal`] can be.
+pub struct Checkpointed<M: Machine, S = <M as Machine>::State, A = <M as Machine>::Action>(
+    pub(super) Traversal<M, S, A>,
+);
+
+impl<M: Machine, S, A> Checkpointed<M, S, A> {
+    /// Periodically write a checkpoint to the given file (see [`Traversal::checkpoint`]),
+    /// e.g. to go on writing checkpoints once resumed.
+    pub fn checkpoint(mut self, path: impl Into<PathBuf>, every: Duration) -> Self {
+        self.0.options.checkpoint = Some(Checkpointing {
+            path: path.into(),
+            every,
+        });
+        self
+    }
+
+    /// Continue from a checkpoint (see [`Traversal::resume_from`]).
+    pub fn resume_from(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let codecs = self.0.codecs.as_ref().unwrap();
+        let checkpoint = Checkpoint::read(path.as_ref(), &codecs.mapped, &codecs.queued())?;
+        self.0.resumed = Some(std::sync::Arc::new(checkpoint));
+        Ok(self)
+    }
+}
+
+impl<M, S, A> Checkpointed<M, S, A>
+where
+    M: Machine,
+    S: Cog + Hash + Eq + 'static,
+    A: Cog + Hash + Eq + 'static,
+{
+    /// See [`Traversal::run`].
+    pub fn run(self) -> Result<TraversalReport, M::Error> {
+        self.0.run()
+    }
+
+    /// See [`Traversal::run_terminal`].
+    pub fn run_terminal(self) -> Result<TerminalSet<S>, M::Error> {
+        self.0.run_terminal()
+    }
+
+    /// See [`Traversal::diagram`].
+    pub fn diagram(self) -> Result<DiGraph<S, A>, M::Error> {
+        self.0.diagram()
+    }
+
+    /// See [`Traversal::check_invariants`].
+    pub fn check_invariants(self) -> Result<Vec<InvariantViolation<S, M::Action>>, M::Error>
+    where
+        M::Action: Hash + Eq,
+    {
+        self.0.check_invariants()
+    }
+
+    /// See [`Traversal::find`].
+    pub fn find(
+        self,
+        predicate: impl Fn(&M::State) -> bool + Send + Sync + 'static,
+    ) -> Result<Option<Witness<S, M::Action>>, M::Error>
+    where
+        M::Action: Hash + Eq,
+    {
+        self.0.find(predicate)
+    }
+
+    /// See [`Traversal::find_all`].
+    pub fn find_all(
+        self,
+        predicate: impl Fn(&M::State) -> bool + Send + Sync + 'static,
+        limit: usize,
+    ) -> Result<Vec<Witness<S, M::Action>>, M::Error>
+    where
+        M::Action: Hash + Eq,
+    {
+        self.0.find_all(predicate, limit)
+    }
+}
+
+impl<M, S, A, P> Checkpointed<ModelChecker<M, P>, ModelCheckerState<S, M::Action>, A>
+where
+    M: Machine + Send + Sync + 'static,
+    M::State: Clone + std::fmt::Debug + Eq + Hash + Send + Sync + 'static,
+    S: Clone + std::fmt::Debug + Eq + Hash + Send + Sync + 'static,
+    M::Action: Clone + std::fmt::Debug + Eq + Hash + Send + Sync + 'static,
+    A: Clone + std::fmt::Debug + Eq + Hash + Exhaustive + Send + Sync + 'static,
+    M::Error: std::fmt::Debug + Send + Sync + 'static,
+    P: crate::logic::PropositionMapping + Send + Sync + 'static,
+    crate::logic::Transition<M>: crate::logic::EvaluatePropositions<P::Proposition>,
+{
+    /// See [`Traversal::model_check`].
+    pub fn model_check(self) -> Result<TraversalReport, ModelCheckerError<M>> {
+        self.0.model_check()
+    }
+
+    /// See [`Traversal::model_check_report`].
+    pub fn model_check_report(self) -> Result<(), String> {
+        self.0.model_check_report()
+    }
+}
+
+/// Where to write checkpoints, and how often.
+#[derive(Clone)]
+pub(crate) struct Checkpointing {
+    pub path: PathBuf,
+    pub every: Duration,
+}
+
+/// Everything needed to resume a traversal, as read back from a checkpoint.
+/// The edges of the graph are only decoded once resumed, since their type
+/// depends on what the traversal is run for.
+#[derive(Clone)]
+pub(crate) struct Checkpoint<S, Q> {
+    total_steps: u64,
+    num_seen: u64,
+    num_terminations: u64,
+    num_edges_skipped: u64,
+    num_edges_pruned: u64,
+    max_depth_seen: u64,
+    /// The time taken before the checkpoint was written
+    pub elapsed: Duration,
+    visited: Vec<(S, (NodeIndex, usize))>,
+    nodes: Vec<S>,
+    edges: Vec<(NodeIndex, NodeIndex, Vec<u8>)>,
+    terminals: Vec<S>,
+    violations: Vec<(usize, NodeIndex)>,
+    deadlocks: Vec<NodeIndex>,
+    found: Vec<NodeIndex>,
+    coverage: Coverage,
+    depths: Vec<DepthStats>,
+    frontier: Vec<Q>,
+}
+
+impl<S, Q> Checkpoint<S, Q> {
+    /// Read a checkpoint written by [`Explorer::write_checkpoint`].
+    pub fn read(path: &Path, states: &Codec<S>, queued: &Codec<Q>) -> io::Result<Self> {
+        let mut r = BufReader::new(File::open(path)?);
+        let mut magic = vec![0; MAGIC.len()];
+        r.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a polestar checkpoint",
+            ));
+        }
+        let r = &mut r;
+        let total_steps = number(r)?;
+        let num_seen = number(r)?;
+        let num_terminations = number(r)?;
+        let num_edges_skipped = number(r)?;
+        let num_edges_pruned = number(r)?;
+        let max_depth_seen = number(r)?;
+        let elapsed = Duration::from_millis(number(r)?);
+        let visited = section(r, |r| {
+            let state = states.decode(&bytes(r)?)?;
+            Ok((state, (node(r)?, number(r)? as usize)))
+        })?;
+        let nodes = section(r, |r| states.decode(&bytes(r)?))?;
+        let edges = section(r, |r| Ok((node(r)?, node(r)?, bytes(r)?)))?;
+        let terminals = section(r, |r| states.decode(&bytes(r)?))?;
+        let violations = section(r, |r| Ok((number(r)? as usize, node(r)?)))?;
+        let deadlocks = section(r, node)?;
+        let found = section(r, node)?;
+        let coverage = section(r, |r| {
+            let kind = string(r)?;
+            let successes = number(r)? as usize;
+            let failures = number(r)? as usize;
+            let errors = section(r, |r| Ok((string(r)?, number(r)? as usize)))?;
+            let errors = errors.into_iter().collect();
+            let coverage = ActionCoverage {
+                successes,
+                failures,
+                errors,
+            };
+            Ok((kind, coverage))
+        })?;
+        let coverage = Coverage {
+            actions: coverage.into_iter().collect(),
+        };
+        let depths = section(r, |r| {
+            Ok(DepthStats {
+                new_states: number(r)? as usize,
+                expanded: number(r)? as usize,
+                successors: number(r)? as usize,
+            })
+        })?;
+        let frontier = section(r, |r| queued.decode(&bytes(r)?))?;
+        let in_graph = |ix: &NodeIndex| ix.index() < nodes.len();
+        if !(visited.iter().all(|(_, (ix, _))| in_graph(ix))
+            && edges
+                .iter()
+                .all(|(from, to, _)| in_graph(from) && in_graph(to))
+            && violations.iter().all(|(_, ix)| in_graph(ix))
+            && deadlocks.iter().all(in_graph)
+            && found.iter().all(in_graph))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "checkpoint refers to nodes which it doesn't hold",
+            ));
+        }
+        Ok(Self {
+            total_steps,
+            num_seen,
+            num_terminations,
+            num_edges_skipped,
+            num_edges_pruned,
+            max_depth_seen,
+            elapsed,
+            visited,
+            nodes,
+            edges,
+            terminals,
+            violations,
+            deadlocks,
+            found,
+            coverage,
+            depths,
+            frontier,
+        })
+    }
+}
+
+impl<M, S, A> Explorer<'_, M, S, A>
+where
+    M: Machine,
+    M::Error: std::fmt::Debug + Send + Sync,
+    S: Cog + Hash + Eq,
+    A: Cog + Hash + Eq,
+{
+    /// Write everything needed to resume the traversal from the given states,
+    /// replacing any previous checkpoint only once the new one is complete.
+    /// This must not be called while any states are being visited.
+    pub(super) fn write_checkpoint(&self, path: &Path, frontier: &[Queued<M>]) -> io::Result<()> {
+        let codecs = self.traversal.codecs.as_ref().unwrap();
+        let queued = self.queued_codec.as_ref().unwrap();
+        let partial = path.with_extension("partial");
+        let mut w = BufWriter::new(File::create(&partial)?);
+        w.write_all(MAGIC)?;
+        let elapsed = self.elapsed_before + self.start_time.elapsed();
+        for n in [
+            self.total_steps.load(SeqCst),
+            self.num_seen.load(SeqCst),
+            self.num_terminations.load(SeqCst),
+            self.num_edges_skipped.load(SeqCst),
+            self.num_edges_pruned.load(SeqCst),
+            self.max_depth_seen.load(SeqCst),
+            elapsed.as_millis() as usize,
+        ] {
+            w.write_all(&(n as u64).to_le_bytes())?;
+        }
+
+        w.write_all(&(self.visited.sum(Visited::len) as u64).to_le_bytes())?;
+        self.visited.try_for_each(|visited| {
+            visited.entries().try_for_each(|(state, (node, depth))| {
+                write_bytes(&mut w, &codecs.mapped.encode(state))?;
+                w.write_all(&(node.index() as u64).to_le_bytes())?;
+                w.write_all(&(depth as u64).to_le_bytes())
+            })
+        })?;
+
+        {
+            let graph = self.graph.lock();
+            w.write_all(&(graph.node_count() as u64).to_le_bytes())?;
+            for state in graph.node_weights() {
+                write_bytes(&mut w, &codecs.mapped.encode(state))?;
+            }
+            w.write_all(&(graph.edge_count() as u64).to_le_bytes())?;
+            for edge in graph.raw_edges() {
+                w.write_all(&(edge.source().index() as u64).to_le_bytes())?;
+                w.write_all(&(edge.target().index() as u64).to_le_bytes())?;
+                write_bytes(&mut w, &codecs.edges.encode(&edge.weight))?;
+            }
+        }
+
+        let terminals = self.terminals.lock();
+        w.write_all(&(terminals.len() as u64).to_le_bytes())?;
+        for state in terminals.iter() {
+            write_bytes(&mut w, &codecs.mapped.encode(state))?;
+        }
+
+        let violations = self.violations.lock();
+        w.write_all(&(violations.len() as u64).to_le_bytes())?;
+        for (invariant, node) in violations.iter() {
+            w.write_all(&(*invariant as u64).to_le_bytes())?;
+            w.write_all(&(node.index() as u64).to_le_bytes())?;
+        }
+
+        let deadlocks = self.deadlocks.lock();
+        w.write_all(&(deadlocks.len() as u64).to_le_bytes())?;
+        for node in deadlocks.iter() {
+            w.write_all(&(node.index() as u64).to_le_bytes())?;
+        }
+
+        let found = self.found.lock();
+        w.write_all(&(found.len() as u64).to_le_bytes())?;
+        for node in found.iter() {
+            w.write_all(&(node.index() as u64).to_le_bytes())?;
+        }
+
+        let coverage = self.coverage.lock();
+        w.write_all(&(coverage.actions.len() as u64).to_le_bytes())?;
+        for (kind, coverage) in coverage.actions.iter() {
+            write_bytes(&mut w, kind.as_bytes())?;
+            w.write_all(&(coverage.successes as u64).to_le_bytes())?;
+            w.write_all(&(coverage.failures as u64).to_le_bytes())?;
+            w.write_all(&(coverage.errors.len() as u64).to_le_bytes())?;
+            for (error, count) in coverage.errors.iter() {
+                write_bytes(&mut w, error.as_bytes())?;
+                w.write_all(&(*count as u64).to_le_bytes())?;
+            }
+        }
+
+        let depths = self.depths.lock();
+        w.write_all(&(depths.len() as u64).to_le_bytes())?;
+        for stats in depths.iter() {
+            for n in [stats.new_states, stats.expanded, stats.successors] {
+                w.write_all(&(n as u64).to_le_bytes())?;
+            }
+        }
+
+        w.write_all(&(frontier.len() as u64).to_le_bytes())?;
+        for item in frontier {
+            write_bytes(&mut w, &queued.encode(item))?;
+        }
+
+        w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(partial, path)
+    }
+
+    /// Restore everything recorded in a checkpoint, and return the states to visit next.
+    ///
+    /// # Panics
+    ///
+    /// If the edges of the graph can't be decoded, because the checkpoint was written
+    /// by a traversal run for something else, e.g. a diagram rather than invariants.
+    pub(super) fn restore(&self, checkpoint: Checkpoint<S, Queued<M>>) -> Vec<Queued<M>> {
+        let Checkpoint {
+            total_steps,
+            num_seen,
+            num_terminations,
+            num_edges_skipped,
+            num_edges_pruned,
+            max_depth_seen,
+            elapsed: _,
+            visited,
+            nodes,
+            edges,
+            terminals,
+            violations,
+            deadlocks,
+            found,
+            coverage,
+            depths,
+            frontier,
+        } = checkpoint;
+        self.total_steps.store(total_steps as usize, SeqCst);
+        self.num_seen.store(num_seen as usize, SeqCst);
+        self.num_terminations
+            .store(num_terminations as usize, SeqCst);
+        self.num_edges_skipped
+            .store(num_edges_skipped as usize, SeqCst);
+        self.num_edges_pruned
+            .store(num_edges_pruned as usize, SeqCst);
+        self.max_depth_seen.store(max_depth_seen as usize, SeqCst);
+
+        self.num_visited.store(visited.len(), SeqCst);
+        for (state, found) in visited {
+            self.visited.lock(&state).insert_new(state, found);
+        }
+        let mut graph = self.graph.lock();
+        for state in nodes {
+            graph.add_node(state);
+        }
+        let codec = &self.traversal.codecs.as_ref().unwrap().edges;
+        for (from, to, edge) in edges {
+            let edge = codec.decode(&edge).unwrap_or_else(|e| {
+                panic!(
+                    "could not decode an edge of the checkpoint, which must be resumed \
+                     by a traversal run for the same purpose as the one which wrote it: {e}"
+                )
+            });
+            let key = (from, to, edge);
+            self.visited_edges.lock(&key).insert(key.clone());
+            let (from, to, edge) = key;
+            graph.add_edge(from, to, edge);
+        }
+        self.terminals.lock().extend(terminals);
+        self.violations.lock().extend(violations);
+        self.deadlocks.lock().extend(deadlocks);
+        self.found.lock().extend(found);
+        *self.coverage.lock() = coverage;
+        *self.depths.lock() = depths;
+        frontier
+    }
+}
+
+fn node(r: &mut impl Read) -> io::Result<NodeIndex> {
+    Ok(NodeIndex::new(number(r)? as usize))
+}
+
+fn string(r: &mut impl Read) -> io::Result<String> {
+    String::from_utf8(bytes(r)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Read a count, followed by that many items.
+fn section<R: Read, T>(
+    r: &mut R,
+    mut item: impl FnMut(&mut R) -> io::Result<T>,
+) -> io::Result<Vec<T>> {
+    let len = number(r)?;
+    (0..len).map(|_| item(r)).collect()
+}
@@ -0,0 +1,61 @@
+//! Coverage of the actions tried during a traversal.
+
+use std::collections::BTreeMap;
+
+/// How often each kind of action succeeded and failed during a traversal
+/// (see [`Traversal::coverage`](super::Traversal::coverage)).
+///
+/// This shows which parts of a model are never exercised: kinds of actions which never
+/// succeed anywhere in the reachable state space are often dead code, or guarded by
+/// a condition in `transition` which is stricter than intended.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// The outcomes of each kind of action, by kind.
+    pub actions: BTreeMap<String, ActionCoverage>,
+}
+
+/// The outcomes of one kind of action (see [`Coverage`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActionCoverage {
+    /// The number of times an action of this kind was taken successfully.
+    pub successes: usize,
+    /// The number of times an action of this kind returned an error.
+    pub failures: usize,
+    /// The number of times each error was returned, by its `Debug` representation.
+    pub errors: BTreeMap<String, usize>,
+}
+
+impl Coverage {
+    /// The kinds of actions which never succeeded in any state visited,
+    /// including those which were never tried at all.
+    pub fn never_succeeded(&self) -> impl Iterator<Item = &str> {
+        self.actions
+            .iter()
+            .filter(|(_, coverage)| coverage.successes == 0)
+            .map(|(kind, _)| kind.as_str())
+    }
+
+    /// Record that an action of the given kind was tried, with the error it returned, if any.
+    pub(super) fn record(&mut self, kind: String, error: Option<String>) {
+        let coverage = self.actions.entry(kind).or_default();
+        match error {
+            None => coverage.successes += 1,
+            Some(error) => {
+                coverage.failures += 1;
+                *coverage.errors.entry(error).or_default() += 1;
+            }
+        }
+    }
+
+    /// Add the counts of another coverage to this one.
+    pub(super) fn merge(&mut self, other: Coverage) {
+        for (kind, other) in other.actions {
+            let coverage = self.actions.entry(kind).or_default();
+            coverage.successes += other.successes;
+            coverage.failures += other.failures;
+            for (error, count) in other.errors {
+                *coverage.errors.entry(error).or_default() += count;
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Progress of a traversal as it runs, and statistics about the states found at each depth.
+
+use std::time::Duration;
+
+/// A snapshot of a traversal in progress (see [`Traversal::progress`](super::Traversal::progress)).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// Total iterations taken so far, including visits to states seen before
+    pub steps: usize,
+    /// Distinct states visited so far
+    pub visited: usize,
+    /// States waiting to be visited
+    pub queued: usize,
+    /// The depth of the state about to be visited
+    pub depth: usize,
+    /// The greatest depth reached so far
+    pub max_depth: usize,
+    /// Time taken so far
+    pub elapsed: Duration,
+    /// Distinct states visited per second, on average so far
+    pub states_per_second: f64,
+    /// A rough estimate of the bytes of memory used by the visited states, the queue and
+    /// the graph, counting only the inline size of each state, as given by `size_of`
+    pub memory_estimate: usize,
+}
+
+/// How many states were found and explored at one depth of a traversal
+/// (see [`TraversalReport::depths`](super::TraversalReport::depths)).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DepthStats {
+    /// States first reached at this depth
+    pub new_states: usize,
+    /// States whose actions were tried at this depth
+    pub expanded: usize,
+    /// Successful transitions from the states expanded at this depth
+    pub successors: usize,
+}
+
+impl DepthStats {
+    /// The average number of successful transitions from each state expanded at this depth.
+    /// A branching factor which stays well above 1 from one depth to the next is a sign
+    /// that the state space is exploding.
+    pub fn branching_factor(&self) -> f64 {
+        if self.expanded == 0 {
+            return 0.0;
+        }
+        self.successors as f64 / self.expanded as f64
+    }
+}
@@ -0,0 +1,124 @@
+//! Checking that a machine refines another, more abstract machine.
+
+use std::hash::Hash;
+
+use itertools::Itertools;
+use petgraph::visit::EdgeRef;
+
+use crate::{util::first, Machine};
+
+use super::Traversal;
+
+/// Why a concrete transition doesn't correspond to a legal abstract transition
+/// (see [`Traversal::check_refinement`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefinementFailure<Q, B, E> {
+    /// Taking one of the abstract actions returned an error.
+    Error {
+        /// The error returned.
+        error: E,
+        /// The abstract state the action was taken in.
+        state: Q,
+        /// The abstract action which returned the error.
+        action: B,
+    },
+    /// The abstract actions led to a different abstract state than the abstraction
+    /// of the state the concrete transition led to. With no abstract actions,
+    /// the abstract state should not have changed.
+    Mismatch {
+        /// The abstraction of the state the concrete transition led to.
+        expected: Q,
+        /// The abstract state the abstract actions led to.
+        actual: Q,
+    },
+}
+
+/// A reachable concrete transition which doesn't correspond to any legal abstract
+/// transition (see [`Traversal::check_refinement`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefinementViolation<S, A, Q, B, E> {
+    /// The state the path starts from.
+    pub initial: S,
+    /// The steps leading from the initial state through the violating transition,
+    /// which is the last one.
+    pub path: Vec<(A, S)>,
+    /// The abstraction of the state the violating transition came from.
+    pub abstract_state: Q,
+    /// The abstract actions the violating action maps to.
+    pub abstract_actions: Vec<B>,
+    /// How the abstract machine failed to follow the violating transition.
+    pub failure: RefinementFailure<Q, B, E>,
+}
+
+impl<M> Traversal<M>
+where
+    M: Machine,
+    M::State: Hash + Eq + 'static,
+    M::Action: Hash + Eq + 'static,
+{
+    /// Check that this machine refines a more abstract one: that every reachable
+    /// transition of this machine corresponds to a legal transition of the abstract
+    /// machine. This is the commutativity law of a
+    /// [`ModelMapping`](crate::mapping::ModelMapping), checked for two machines.
+    ///
+    /// For each transition from a state `s` to `s'` by an action `a`, taking the actions
+    /// `abstract_actions(a)`, one after another, from `abstract_state(s)` must succeed,
+    /// and lead to `abstract_state(s')`. An action may map to no abstract actions at all,
+    /// which is a stuttering step, in which case the abstract state must not change.
+    ///
+    /// Returns the violating transition closest to the initial states, with a shortest
+    /// path through it, or None if this machine refines the abstract one.
+    /// The transitions checked are those between states as mapped
+    /// (see [`Traversal::map_state`]), and self-loops are not checked with
+    /// [`Traversal::ignore_loopbacks`].
+    #[allow(clippy::type_complexity)]
+    pub fn check_refinement<AM>(
+        self,
+        abstract_machine: &AM,
+        abstract_state: impl Fn(&M::State) -> AM::State,
+        abstract_actions: impl Fn(&M::Action) -> Vec<AM::Action>,
+    ) -> Result<
+        Option<RefinementViolation<M::State, M::Action, AM::State, AM::Action, AM::Error>>,
+        M::Error,
+    >
+    where
+        AM: Machine,
+        AM::State: Clone + PartialEq,
+        AM::Action: Clone,
+    {
+        let (_, graph, paths) = self.traverse_with_paths()?;
+
+        let edges = graph
+            .edge_references()
+            .filter_map(|edge| Some((paths.order.get(&edge.source())?, edge.id(), edge)))
+            .sorted_by_key(|(order, id, _)| (**order, *id));
+        for (_, _, edge) in edges {
+            let from = abstract_state(&graph[edge.source()]);
+            let expected = abstract_state(&graph[edge.target()]);
+            let actions = abstract_actions(edge.weight());
+            let failure = match abstract_machine
+                .apply_actions(from.clone(), actions.clone())
+                .map(first)
+            {
+                Ok(actual) if actual == expected => continue,
+                Ok(actual) => RefinementFailure::Mismatch { expected, actual },
+                Err((error, state, action)) => RefinementFailure::Error {
+                    error,
+                    state,
+                    action,
+                },
+            };
+
+            let (start, mut path) = paths.path_to(&graph, edge.source());
+            path.push((edge.weight().clone(), graph[edge.target()].clone()));
+            return Ok(Some(RefinementViolation {
+                initial: graph[start].clone(),
+                path,
+                abstract_state: from,
+                abstract_actions: actions,
+                failure,
+            }));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,293 @@
+//! Cost-guided search for the cheapest paths to a goal.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt::Debug,
+    hash::Hash,
+    sync::OnceLock,
+};
+
+use crate::machine::Cog;
+use crate::{util::first, Machine};
+
+use super::Traversal;
+
+/// A cost which a search can minimize (see [`Traversal::search_min_cost`]).
+/// Costs are summed along a path, and compared in order to find the cheapest.
+///
+/// A cost can have several dimensions, like a tuple. The search orders the costs
+/// of such paths lexicographically, and [`Traversal::search_pareto_frontier`] finds
+/// the paths whose costs are not beaten in every dimension at once by another path.
+pub trait Cost: Clone + Debug + Ord + Send + Sync + 'static {
+    /// The cost of an empty path.
+    fn zero() -> Self;
+
+    /// The sum of two costs.
+    fn plus(&self, other: &Self) -> Self;
+
+    /// Whether this cost is no greater than the other in every dimension.
+    /// With a single dimension, this is just `<=`.
+    fn dominates(&self, other: &Self) -> bool {
+        self <= other
+    }
+}
+
+macro_rules! cost_numbers {
+    ($($t:ty),*) => {
+        $(
+            impl Cost for $t {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn plus(&self, other: &Self) -> Self {
+                    self + other
+                }
+            }
+        )*
+    };
+}
+
+cost_numbers!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+macro_rules! cost_tuples {
+    ($(($($t:ident $i:tt),+))*) => {
+        $(
+            impl<$($t: Cost),+> Cost for ($($t,)+) {
+                fn zero() -> Self {
+                    ($($t::zero(),)+)
+                }
+
+                fn plus(&self, other: &Self) -> Self {
+                    ($(self.$i.plus(&other.$i),)+)
+                }
+
+                fn dominates(&self, other: &Self) -> bool {
+                    $(self.$i.dominates(&other.$i))&&+
+                }
+            }
+        )*
+    };
+}
+
+cost_tuples! {
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+}
+
+impl<T: Cost, const N: usize> Cost for [T; N] {
+    fn zero() -> Self {
+        std::array::from_fn(|_| T::zero())
+    }
+
+    fn plus(&self, other: &Self) -> Self {
+        std::array::from_fn(|i| self[i].plus(&other[i]))
+    }
+
+    fn dominates(&self, other: &Self) -> bool {
+        self.iter().zip(other).all(|(a, b)| a.dominates(b))
+    }
+}
+
+/// A path to a goal found by a cost-guided search, along with its cost
+/// (see [`Traversal::search_min_cost`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostedPath<S, A, C> {
+    /// The total cost of the actions along the path.
+    pub cost: C,
+    /// The state the path starts from.
+    pub initial: S,
+    /// The steps leading from the initial state to the goal,
+    /// which is the last one, or the initial state if there are none.
+    pub path: Vec<(A, S)>,
+}
+
+impl<S, A, C> CostedPath<S, A, C> {
+    /// The goal state which the path reaches.
+    pub fn goal(&self) -> &S {
+        self.path.last().map_or(&self.initial, |(_, s)| s)
+    }
+}
+
+/// A path found so far, as the step which extends an earlier one
+struct Label<M: Machine, C> {
+    state: M::State,
+    cost: C,
+    depth: usize,
+    parent: Option<(usize, M::Action)>,
+    /// Whether a cheaper path to the same state has been found since
+    dominated: bool,
+}
+
+impl<M, S, A> Traversal<M, S, A>
+where
+    M: Machine,
+    M::State: Cog,
+    M::Action: Cog,
+    S: Cog + Hash + Eq,
+{
+    /// Search for the cheapest paths from an initial state to a goal state,
+    /// with Dijkstra's algorithm, or A* if a heuristic is given.
+    /// `cost(state, action, next)` is the cost of taking an action, which must never be
+    /// negative. Every goal state which is reached at the lowest cost is returned, each
+    /// with one cheapest path, in the order found, or none if no goal can be reached.
+    ///
+    /// `heuristic(state)` is an estimate of the cost of the rest of the cheapest path from
+    /// a state to a goal, such as `|_| 0`, in which case this is Dijkstra's algorithm.
+    /// For the paths found to be cheapest, it must never overestimate that cost,
+    /// nor decrease by more than the cost of any one action.
+    ///
+    /// States are identified as mapped (see [`Traversal::map_state`]), and are not explored
+    /// beyond terminal states or [`Traversal::max_depth`]. The search runs on one thread,
+    /// in memory, whatever the strategy and other options of the traversal.
+    #[allow(clippy::type_complexity)]
+    pub fn search_min_cost<C: Cost>(
+        self,
+        cost: impl Fn(&M::State, &M::Action, &M::State) -> C,
+        goal: impl Fn(&M::State) -> bool,
+        heuristic: impl Fn(&M::State) -> C,
+    ) -> Result<Vec<CostedPath<M::State, M::Action, C>>, M::Error> {
+        self.search(cost, goal, heuristic, |a: &C, b: &C| a <= b, true)
+    }
+
+    /// Search for the cheapest paths from an initial state to a goal state, like
+    /// [`Traversal::search_min_cost`], when the cost has several dimensions which can't
+    /// be traded off against each other, e.g. time and money.
+    ///
+    /// Every path returned has a distinct cost which is not beaten in every dimension
+    /// at once by any other path to a goal: together, they are the Pareto frontier.
+    /// The heuristic must satisfy the conditions of `search_min_cost` in every dimension.
+    #[allow(clippy::type_complexity)]
+    pub fn search_pareto_frontier<C: Cost>(
+        self,
+        cost: impl Fn(&M::State, &M::Action, &M::State) -> C,
+        goal: impl Fn(&M::State) -> bool,
+        heuristic: impl Fn(&M::State) -> C,
+    ) -> Result<Vec<CostedPath<M::State, M::Action, C>>, M::Error> {
+        self.search(cost, goal, heuristic, C::dominates, false)
+    }
+
+    /// Best-first search, keeping every path found to a state unless another path to it
+    /// is at least as good, according to `beats`. Goals reached at the same cost as one
+    /// already found are kept only if `keep_ties` is set.
+    #[allow(clippy::type_complexity)]
+    fn search<C: Cost>(
+        self,
+        cost: impl Fn(&M::State, &M::Action, &M::State) -> C,
+        goal: impl Fn(&M::State) -> bool,
+        heuristic: impl Fn(&M::State) -> C,
+        beats: impl Fn(&C, &C) -> bool,
+        keep_ties: bool,
+    ) -> Result<Vec<CostedPath<M::State, M::Action, C>>, M::Error> {
+        let all_actions = OnceLock::new();
+        let mut labels: Vec<Label<M, C>> = vec![];
+        // The labels of the paths to each state which are not beaten by another
+        let mut frontier: HashMap<S, Vec<usize>> = HashMap::new();
+        // Ordered by estimated total cost, then by the order found
+        let mut queue = BinaryHeap::new();
+        let mut goals: Vec<usize> = vec![];
+
+        // Record a path unless it is beaten, returning its place in the queue
+        let mut record = |labels: &mut Vec<Label<M, C>>, label: Label<M, C>| {
+            let mapped = self.mapped(label.state.clone())?;
+            let found = frontier.entry(mapped).or_default();
+            if found
+                .iter()
+                .any(|&other| beats(&labels[other].cost, &label.cost))
+            {
+                return None;
+            }
+            found.retain(|&other| {
+                let beaten = beats(&label.cost, &labels[other].cost);
+                labels[other].dominated |= beaten;
+                !beaten
+            });
+            let estimate = label.cost.plus(&heuristic(&label.state));
+            let ix = labels.len();
+            found.push(ix);
+            labels.push(label);
+            Some(Reverse((estimate, ix)))
+        };
+
+        for state in self.initial.iter() {
+            let label = Label {
+                state: state.clone(),
+                cost: C::zero(),
+                depth: 0,
+                parent: None,
+                dominated: false,
+            };
+            queue.extend(record(&mut labels, label));
+        }
+
+        while let Some(Reverse((estimate, ix))) = queue.pop() {
+            if labels[ix].dominated {
+                continue;
+            }
+            // No path from here can do better than the goals already found
+            if goals.iter().any(|&g| {
+                beats(&labels[g].cost, &estimate) && !(keep_ties && labels[g].cost == estimate)
+            }) {
+                continue;
+            }
+            let state = labels[ix].state.clone();
+            if goal(&state) {
+                goals.push(ix);
+                continue;
+            }
+            let depth = labels[ix].depth;
+            if self.machine.is_terminal(&state)
+                || depth >= self.options.max_depth.unwrap_or(usize::MAX)
+            {
+                continue;
+            }
+            for action in self.actions_in(&state, &all_actions).iter() {
+                match self
+                    .machine
+                    .transition(state.clone(), action.clone())
+                    .map(first)
+                {
+                    Ok(next) => {
+                        let label = Label {
+                            cost: labels[ix].cost.plus(&cost(&state, action, &next)),
+                            state: next,
+                            depth: depth + 1,
+                            parent: Some((ix, action.clone())),
+                            dominated: false,
+                        };
+                        queue.extend(record(&mut labels, label));
+                    }
+                    Err(err) => {
+                        if (self.is_fatal_error)(&err) {
+                            return Err(err);
+                        }
+                        if self.options.trace_errors {
+                            tracing::error!(?err, ?action, ?state, "edge skipped");
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(goals
+            .into_iter()
+            .map(|ix| {
+                let cost = labels[ix].cost.clone();
+                let mut ix = ix;
+                let mut path = vec![];
+                while let Some((parent, action)) = &labels[ix].parent {
+                    path.push((action.clone(), labels[ix].state.clone()));
+                    ix = *parent;
+                }
+                path.reverse();
+                CostedPath {
+                    cost,
+                    initial: labels[ix].state.clone(),
+                    path,
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,54 @@
+//! A collection split into independently locked shards.
+
+use std::hash::{BuildHasher, Hash, RandomState};
+
+use parking_lot::{Mutex, MutexGuard};
+
+/// A collection split into shards which are locked independently, each key belonging
+/// to the shard selected by its hash, so that threads visiting different states
+/// rarely contend for the same lock.
+pub(crate) struct Sharded<T> {
+    shards: Box<[Mutex<T>]>,
+    hasher: RandomState,
+}
+
+impl<T: Default> Sharded<T> {
+    /// Create enough shards to keep contention low between the given number of threads.
+    pub fn new(threads: usize) -> Self {
+        Self::new_with(threads, |_| T::default())
+    }
+}
+
+impl<T> Sharded<T> {
+    /// Like [`Sharded::new`], creating each shard with a function
+    /// which is given the number of shards.
+    pub fn new_with(threads: usize, shard: impl Fn(usize) -> T) -> Self {
+        let num_shards = if threads <= 1 {
+            1
+        } else {
+            (threads * 8).next_power_of_two()
+        };
+        Self {
+            shards: (0..num_shards)
+                .map(|_| Mutex::new(shard(num_shards)))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Lock the shard which the key belongs to.
+    pub fn lock(&self, key: &impl Hash) -> MutexGuard<'_, T> {
+        let hash = self.hasher.hash_one(key) as usize;
+        self.shards[hash & (self.shards.len() - 1)].lock()
+    }
+
+    /// Lock each shard in turn, and sum up some measure of them.
+    pub fn sum<N: std::iter::Sum>(&self, f: impl Fn(&T) -> N) -> N {
+        self.shards.iter().map(|shard| f(&shard.lock())).sum()
+    }
+
+    /// Lock each shard in turn, stopping at the first error.
+    pub fn try_for_each<E>(&self, mut f: impl FnMut(&T) -> Result<(), E>) -> Result<(), E> {
+        self.shards.iter().try_for_each(|shard| f(&shard.lock()))
+    }
+}
@@ -0,0 +1,234 @@
+//! Random simulation of a machine, for state spaces too large to explore exhaustively.
+
+use std::{collections::HashSet, hash::Hash, sync::OnceLock};
+
+use proptest::prelude::RngExt;
+use proptest::test_runner::{Config, RngAlgorithm, TestRng, TestRunner};
+
+use crate::machine::Cog;
+use crate::{util::first, Machine};
+
+use super::{Traversal, VisitType};
+
+/// How many arbitrary actions to try in each state, to choose between
+const ARBITRARY_CANDIDATES: usize = 8;
+
+/// Why a random walk failed (see [`Traversal::simulate`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalkFailure<E> {
+    /// The named invariant does not hold in the last state (see [`Traversal::invariant`]).
+    Invariant(String),
+    /// No action could be taken in the last state, which is not terminal
+    /// (see [`Traversal::check_deadlock`]).
+    Deadlock,
+    /// The visitor returned an error in the last state, or taking an action in it
+    /// returned a fatal error (see [`Traversal::is_fatal_error`]).
+    Error(E),
+}
+
+/// A random walk which failed, with the path it took (see [`Traversal::simulate`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedWalk<S, A, E> {
+    /// The number of the walk, counting from 0.
+    pub walk: usize,
+    /// Why the walk failed.
+    pub failure: WalkFailure<E>,
+    /// The state the walk started from.
+    pub initial: S,
+    /// The steps leading from the initial state to the failing state,
+    /// which is the last one, or the initial state if there are none.
+    pub path: Vec<(A, S)>,
+}
+
+impl<S, A, E> FailedWalk<S, A, E> {
+    /// The state in which the walk failed.
+    pub fn state(&self) -> &S {
+        self.path.last().map_or(&self.initial, |(_, s)| s)
+    }
+}
+
+/// The outcome of a random simulation (see [`Traversal::simulate`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simulation<S, A, E> {
+    /// The seed which reproduces the simulation.
+    pub seed: u64,
+    /// Total walks taken
+    pub num_walks: usize,
+    /// Total actions taken, over all walks
+    pub total_steps: usize,
+    /// Distinct states visited, as mapped (see [`Traversal::map_state`])
+    pub num_visited: usize,
+    /// Total actions tried which returned an error
+    pub num_edges_skipped: usize,
+    /// Every walk which failed, in order.
+    pub failures: Vec<FailedWalk<S, A, E>>,
+}
+
+impl<M, S, A> Traversal<M, S, A>
+where
+    M: Machine,
+    M::State: Cog,
+    M::Action: Cog,
+    S: Cog + Hash + Eq,
+{
+    /// Take `walks` random walks of up to `steps` actions each, from randomly chosen
+    /// initial states, checking invariants (see [`Traversal::invariant`]) and calling
+    /// the visitor in every state along the way. This samples state spaces which are
+    /// too large to explore exhaustively.
+    ///
+    /// In each state, the actions tried are those listed by the machine or [`Exhaustive`],
+    /// or a few arbitrary ones (see [`Traversal::arbitrary_actions`]), and one of those
+    /// which succeed is taken at random, preferring those which lead to states not yet
+    /// visited by any walk. A walk ends early at a terminal state, or where no action
+    /// succeeds, or when it fails.
+    ///
+    /// A walk fails if an invariant does not hold, the visitor or a fatal error
+    /// (see [`Traversal::is_fatal_error`]) returns an error, or, with
+    /// [`Traversal::check_deadlock`], no action succeeds in a state which isn't terminal.
+    /// Deadlocks are not reported with arbitrary actions, since they only sample
+    /// the actions which might succeed.
+    ///
+    /// The simulation runs on one thread, and the same seed always takes the same walks,
+    /// so every failure can be reproduced.
+    ///
+    /// [`Exhaustive`]: exhaustive::Exhaustive
+    #[allow(clippy::type_complexity)]
+    pub fn simulate(
+        self,
+        walks: usize,
+        steps: usize,
+        seed: u64,
+    ) -> Simulation<M::State, M::Action, M::Error> {
+        self.simulate_visiting(walks, steps, seed, |_| {})
+    }
+
+    /// Like [`Traversal::simulate`], calling `visit` with each state visited, as mapped,
+    /// every time it is visited
+    #[allow(clippy::type_complexity)]
+    pub(super) fn simulate_visiting(
+        self,
+        walks: usize,
+        steps: usize,
+        seed: u64,
+        mut visit: impl FnMut(&S),
+    ) -> Simulation<M::State, M::Action, M::Error> {
+        let mut seed_bytes = [0; 32];
+        seed_bytes[..8].copy_from_slice(&seed.to_le_bytes());
+        let rng = TestRng::from_seed(RngAlgorithm::ChaCha, &seed_bytes);
+        let mut runner = TestRunner::new_with_rng(Config::default(), rng);
+        let all_actions = OnceLock::new();
+        let mut visited: HashSet<S> = HashSet::new();
+        let mut simulation = Simulation {
+            seed,
+            num_walks: 0,
+            total_steps: 0,
+            num_visited: 0,
+            num_edges_skipped: 0,
+            failures: vec![],
+        };
+
+        for walk in 0..walks {
+            if self.initial.is_empty() {
+                break;
+            }
+            simulation.num_walks += 1;
+            let initial = self.initial[runner.rng().random_range(0..self.initial.len())].clone();
+            let mut state = initial.clone();
+            let mut path = vec![];
+            let failure = loop {
+                let Some(mapped) = self.mapped(state.clone()) else {
+                    break None;
+                };
+                visit(&mapped);
+                visited.insert(mapped);
+                if let Some((name, _)) = self.invariants.iter().find(|(_, inv)| !inv(&state)) {
+                    break Some(WalkFailure::Invariant(name.clone()));
+                }
+                if self.machine.is_terminal(&state) {
+                    break (self.visitor)(&state, VisitType::Terminal)
+                        .err()
+                        .map(WalkFailure::Error);
+                }
+                if path.len() >= steps {
+                    break (self.visitor)(&state, VisitType::Normal)
+                        .err()
+                        .map(WalkFailure::Error);
+                }
+
+                let candidates = match &self.arbitrary_action {
+                    Some(arbitrary) => (0..ARBITRARY_CANDIDATES)
+                        .map(|_| arbitrary(&mut runner))
+                        .collect(),
+                    None => self.actions_in(&state, &all_actions).into_owned(),
+                };
+                let mut enabled = vec![];
+                let mut fatal = None;
+                for action in candidates {
+                    match self
+                        .machine
+                        .transition(state.clone(), action.clone())
+                        .map(first)
+                    {
+                        Ok(next) => enabled.push((action, next)),
+                        Err(err) => {
+                            simulation.num_edges_skipped += 1;
+                            if (self.is_fatal_error)(&err) {
+                                fatal = Some(err);
+                                break;
+                            }
+                            if self.options.trace_errors {
+                                tracing::error!(?err, ?action, ?state, "edge skipped");
+                            }
+                        }
+                    }
+                }
+                if let Some(err) = fatal {
+                    break Some(WalkFailure::Error(err));
+                }
+
+                if enabled.is_empty() {
+                    if let Err(err) = (self.visitor)(&state, VisitType::Deadlock) {
+                        break Some(WalkFailure::Error(err));
+                    }
+                    let deadlock = self.options.check_deadlock && self.arbitrary_action.is_none();
+                    break deadlock.then_some(WalkFailure::Deadlock);
+                }
+                if let Err(err) = (self.visitor)(&state, VisitType::Normal) {
+                    break Some(WalkFailure::Error(err));
+                }
+
+                // Prefer to go somewhere new
+                let unvisited = enabled
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, next))| {
+                        self.mapped(next.clone())
+                            .is_some_and(|m| !visited.contains(&m))
+                    })
+                    .map(|(i, _)| i)
+                    .collect::<Vec<_>>();
+                let choice = if unvisited.is_empty() {
+                    runner.rng().random_range(0..enabled.len())
+                } else {
+                    unvisited[runner.rng().random_range(0..unvisited.len())]
+                };
+                let (action, next) = enabled.swap_remove(choice);
+                simulation.total_steps += 1;
+                path.push((action, next.clone()));
+                state = next;
+            };
+
+            if let Some(failure) = failure {
+                simulation.failures.push(FailedWalk {
+                    walk,
+                    failure,
+                    initial,
+                    path,
+                });
+            }
+        }
+
+        simulation.num_visited = visited.len();
+        simulation
+    }
+}
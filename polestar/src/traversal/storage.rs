@@ -0,0 +1,854 @@
+//! Storage for the visited states and the frontier of a traversal,
+//! optionally spilling to files on disk when there are too many to keep in memory.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    hash::{BuildHasher, Hash, RandomState},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use petgraph::graph::NodeIndex;
+
+/// Converts values to and from bytes, for storing them on disk.
+#[allow(clippy::type_complexity)]
+pub(crate) struct Codec<T> {
+    encode: Arc<dyn Fn(&T) -> Vec<u8> + Send + Sync>,
+    decode: Arc<dyn Fn(&[u8]) -> io::Result<T> + Send + Sync>,
+}
+
+impl<T> Codec<T> {
+    pub fn new(
+        encode: impl Fn(&T) -> Vec<u8> + Send + Sync + 'static,
+        decode: impl Fn(&[u8]) -> io::Result<T> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            encode: Arc::new(encode),
+            decode: Arc::new(decode),
+        }
+    }
+
+    pub fn encode(&self, value: &T) -> Vec<u8> {
+        (self.encode)(value)
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> io::Result<T> {
+        (self.decode)(bytes)
+    }
+}
+
+#[cfg(feature = "recording")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned + 'static> Codec<T> {
+    /// Encode values as JSON.
+    pub fn json() -> Self {
+        Self::new(
+            |value| serde_json::to_vec(value).expect("failed to encode value as JSON"),
+            |bytes| Ok(serde_json::from_slice(bytes)?),
+        )
+    }
+}
+
+impl<T: 'static> Codec<T> {
+    /// A codec for another type, which is converted to and from this one.
+    pub fn map<U>(
+        self,
+        into: impl Fn(&U) -> T + Send + Sync + 'static,
+        from: impl Fn(T) -> U + Send + Sync + 'static,
+    ) -> Codec<U> {
+        let decode = self.clone();
+        Codec::new(
+            move |value| self.encode(&into(value)),
+            move |bytes| decode.decode(bytes).map(&from),
+        )
+    }
+}
+
+impl<T: 'static> Codec<T> {
+    /// A codec for pairs of values.
+    pub fn pair<U: 'static>(self, other: Codec<U>) -> Codec<(T, U)> {
+        let decoders = (self.clone(), other.clone());
+        Codec::new(
+            move |(a, b)| {
+                let mut bytes = vec![];
+                or_panic(write_bytes(&mut bytes, &self.encode(a)));
+                or_panic(write_bytes(&mut bytes, &other.encode(b)));
+                bytes
+            },
+            move |mut r| {
+                let a = decoders.0.decode(&bytes(&mut r)?)?;
+                let b = decoders.1.decode(&bytes(&mut r)?)?;
+                Ok((a, b))
+            },
+        )
+    }
+}
+
+impl<T> Clone for Codec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            encode: self.encode.clone(),
+            decode: self.decode.clone(),
+        }
+    }
+}
+
+/// Codecs for each of the types involved in a traversal: its mapped states `S`,
+/// the machine's states and actions, and the graph's edges `A`.
+pub(crate) struct Codecs<S, St, Ac, A> {
+    pub mapped: Codec<S>,
+    pub states: Codec<St>,
+    pub actions: Codec<Ac>,
+    pub edges: Codec<A>,
+}
+
+impl<S, St, Ac, A> Clone for Codecs<S, St, Ac, A> {
+    fn clone(&self) -> Self {
+        Self {
+            mapped: self.mapped.clone(),
+            states: self.states.clone(),
+            actions: self.actions.clone(),
+            edges: self.edges.clone(),
+        }
+    }
+}
+
+impl<S, St: 'static, Ac: 'static, A> Codecs<S, St, Ac, A> {
+    /// Replace the codec for the edges.
+    pub fn with_edges<AA>(self, edges: Codec<AA>) -> Codecs<S, St, Ac, AA> {
+        Codecs {
+            mapped: self.mapped,
+            states: self.states,
+            actions: self.actions,
+            edges,
+        }
+    }
+
+    /// A codec for a queued state, with the node and action it was reached from,
+    /// and its depth.
+    #[allow(clippy::type_complexity)]
+    pub fn queued(&self) -> Codec<(St, Option<(NodeIndex, Ac)>, usize)> {
+        let (states, actions) = (self.states.clone(), self.actions.clone());
+        let decoders = (self.states.clone(), self.actions.clone());
+        Codec::new(
+            move |(state, prev, depth): &(St, Option<(NodeIndex, Ac)>, usize)| {
+                let mut bytes = vec![];
+                or_panic(write_bytes(&mut bytes, &states.encode(state)));
+                bytes.extend((*depth as u64).to_le_bytes());
+                if let Some((node, action)) = prev {
+                    bytes.extend((node.index() as u64).to_le_bytes());
+                    or_panic(write_bytes(&mut bytes, &actions.encode(action)));
+                }
+                bytes
+            },
+            move |mut r| {
+                let (states, actions) = &decoders;
+                let state = states.decode(&bytes(&mut r)?)?;
+                let depth = number(&mut r)?;
+                let prev = match read_u64(&mut r)? {
+                    Some(node) => Some((
+                        NodeIndex::new(node as usize),
+                        actions.decode(&bytes(&mut r)?)?,
+                    )),
+                    None => None,
+                };
+                Ok((state, prev, depth as usize))
+            },
+        )
+    }
+}
+
+/// Where to spill a traversal's visited states and queued states,
+/// and how many of each to keep in memory.
+#[derive(Clone)]
+pub(crate) struct Spill {
+    pub dir: PathBuf,
+    pub max_in_memory: usize,
+}
+
+/// A directory holding the files spilled by one traversal,
+/// which is removed along with its contents once the traversal is done.
+pub(crate) struct SpillDir {
+    path: PathBuf,
+    next_file: AtomicUsize,
+    /// The number of states written to disk, not counting rewrites when merging
+    pub num_spilled: AtomicUsize,
+}
+
+impl SpillDir {
+    /// Create a fresh directory within the given one.
+    pub fn create(parent: &Path) -> io::Result<Arc<Self>> {
+        static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+        let path = parent.join(format!(
+            "polestar-{}-{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, SeqCst)
+        ));
+        fs::create_dir_all(&path)?;
+        Ok(Arc::new(Self {
+            path,
+            next_file: AtomicUsize::new(0),
+            num_spilled: AtomicUsize::new(0),
+        }))
+    }
+
+    fn new_file(&self, kind: &str) -> PathBuf {
+        let n = self.next_file.fetch_add(1, SeqCst);
+        self.path.join(format!("{kind}-{n}"))
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// The visited states of a traversal, each with its node in the graph
+/// and the shallowest depth it was visited at.
+pub(crate) struct Visited<S> {
+    len: usize,
+    /// The expected number of states wrongly taken to have been visited already
+    expected_omissions: f64,
+    store: Store<S>,
+}
+
+enum Store<S> {
+    /// The states themselves, some of which may be spilled to disk
+    Exact {
+        memory: HashMap<S, (NodeIndex, usize)>,
+        disk: Option<VisitedDisk<S>>,
+    },
+    /// A 64-bit fingerprint of each state
+    Fingerprints {
+        hasher: RandomState,
+        memory: HashMap<u64, (NodeIndex, usize)>,
+    },
+    /// A few bits set for each state, with no node or depth
+    Bits { hasher: RandomState, bits: Bloom },
+}
+
+struct VisitedDisk<S> {
+    dir: Arc<SpillDir>,
+    codec: Codec<S>,
+    max_in_memory: usize,
+    hasher: RandomState,
+    /// Sorted runs of spilled states, oldest first.
+    /// Where a state appears more than once, the newest entry is the current one.
+    runs: Vec<Run>,
+}
+
+impl<S> Visited<S> {
+    fn new(store: Store<S>) -> Self {
+        Self {
+            len: 0,
+            expected_omissions: 0.0,
+            store,
+        }
+    }
+
+    /// A set which is kept entirely in memory.
+    pub fn in_memory() -> Self {
+        Self::new(Store::Exact {
+            memory: HashMap::new(),
+            disk: None,
+        })
+    }
+
+    /// A set which writes its states out to sorted files in the directory
+    /// whenever more than `max_in_memory` are held in memory.
+    pub fn spilling(dir: Arc<SpillDir>, codec: Codec<S>, max_in_memory: usize) -> Self {
+        Self::new(Store::Exact {
+            memory: HashMap::new(),
+            disk: Some(VisitedDisk {
+                dir,
+                codec,
+                max_in_memory: max_in_memory.max(1),
+                hasher: RandomState::new(),
+                runs: vec![],
+            }),
+        })
+    }
+
+    /// A set which only stores a 64-bit fingerprint of each state,
+    /// so that a state whose fingerprint collides with a visited one is missed.
+    pub fn fingerprints() -> Self {
+        Self::new(Store::Fingerprints {
+            hasher: RandomState::new(),
+            memory: HashMap::new(),
+        })
+    }
+
+    /// A set which only sets `hashes` bits in an array of `num_bits` bits for each state,
+    /// so that a state whose bits have all been set by other states is missed.
+    /// Nodes and depths are not recorded.
+    pub fn bitstate(num_bits: usize, hashes: u32) -> Self {
+        Self::new(Store::Bits {
+            hasher: RandomState::new(),
+            bits: Bloom::with_bits(num_bits, hashes),
+        })
+    }
+
+    /// The number of distinct states visited.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Each state visited, with its node and depth,
+    /// for a set which is kept entirely in memory.
+    pub fn entries(&self) -> impl Iterator<Item = (&S, (NodeIndex, usize))> {
+        let Store::Exact { memory, disk: None } = &self.store else {
+            panic!("only the states of an exact, unspilled set can be listed")
+        };
+        memory.iter().map(|(state, found)| (state, *found))
+    }
+
+    /// A rough estimate of the bytes of memory used, counting only the inline size
+    /// of each state.
+    pub fn memory_estimate(&self) -> usize {
+        match &self.store {
+            Store::Exact { memory, .. } => {
+                memory.capacity() * std::mem::size_of::<(S, (NodeIndex, usize))>()
+            }
+            Store::Fingerprints { memory, .. } => {
+                memory.capacity() * std::mem::size_of::<(u64, (NodeIndex, usize))>()
+            }
+            Store::Bits { bits, .. } => bits.bits.len() * std::mem::size_of::<u64>(),
+        }
+    }
+
+    /// The expected number of states which were missed, because they were
+    /// mistaken for states already visited. Always zero for an exact set.
+    pub fn expected_omissions(&self) -> f64 {
+        self.expected_omissions
+    }
+}
+
+impl<S: Hash + Eq> Visited<S> {
+    /// Look up a visited state.
+    pub fn get(&mut self, state: &S) -> Option<(NodeIndex, usize)> {
+        match &mut self.store {
+            Store::Exact { memory, disk } => {
+                if let Some(found) = memory.get(state) {
+                    return Some(*found);
+                }
+                let disk = disk.as_mut()?;
+                let hash = disk.hasher.hash_one(state);
+                let decode = &disk.codec.decode;
+                disk.runs.iter_mut().rev().find_map(|run| {
+                    or_panic(run.find(hash, |bytes| or_panic(decode(bytes)) == *state))
+                })
+            }
+            Store::Fingerprints { hasher, memory } => memory.get(&hasher.hash_one(state)).copied(),
+            Store::Bits { hasher, bits } => bits
+                .contains(hasher.hash_one(state))
+                .then_some((NodeIndex::end(), 0)),
+        }
+    }
+
+    /// Record a state which was not visited before.
+    pub fn insert_new(&mut self, state: S, found: (NodeIndex, usize)) {
+        // The chance that this state would have been missed, had it been visited now
+        self.expected_omissions += match &self.store {
+            Store::Exact { .. } => 0.0,
+            Store::Fingerprints { memory, .. } => memory.len() as f64 / 2f64.powi(64),
+            Store::Bits { bits, .. } => bits.false_positive_rate(),
+        };
+        self.len += 1;
+        self.update(state, found);
+    }
+
+    /// Record a new node or depth for a state which was visited before.
+    pub fn update(&mut self, state: S, found: (NodeIndex, usize)) {
+        match &mut self.store {
+            Store::Exact { memory, disk } => {
+                memory.insert(state, found);
+                if let Some(disk) = disk.as_mut()
+                    && memory.len() >= disk.max_in_memory
+                {
+                    or_panic(disk.spill(memory));
+                }
+            }
+            Store::Fingerprints { hasher, memory } => {
+                memory.insert(hasher.hash_one(&state), found);
+            }
+            Store::Bits { hasher, bits } => bits.insert(hasher.hash_one(&state)),
+        }
+    }
+}
+
+impl<S: Hash + Eq> VisitedDisk<S> {
+    /// Write out all of the states in memory as a new run, merging it with
+    /// the previous runs for as long as they are no bigger than twice its size,
+    /// so that there are only logarithmically many runs to search.
+    fn spill(&mut self, memory: &mut HashMap<S, (NodeIndex, usize)>) -> io::Result<()> {
+        let mut entries = memory
+            .drain()
+            .map(|(state, (node, depth))| Entry {
+                hash: self.hasher.hash_one(&state),
+                node: node.index() as u64,
+                depth: depth as u64,
+                bytes: (self.codec.encode)(&state),
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry.hash);
+        self.dir.num_spilled.fetch_add(entries.len(), SeqCst);
+
+        let mut run = Run::write(
+            self.dir.new_file("visited"),
+            entries.len(),
+            entries.into_iter().map(Ok),
+        )?;
+        while let Some(older) = self.runs.pop() {
+            if older.len > 2 * run.len {
+                self.runs.push(older);
+                break;
+            }
+            run = Run::merge(self.dir.new_file("visited"), run, older)?;
+        }
+        self.runs.push(run);
+        Ok(())
+    }
+}
+
+/// A spilled state, as written in a run.
+struct Entry {
+    hash: u64,
+    node: u64,
+    depth: u64,
+    bytes: Vec<u8>,
+}
+
+impl Entry {
+    fn write(&self, w: &mut impl Write) -> io::Result<u64> {
+        w.write_all(&self.hash.to_le_bytes())?;
+        w.write_all(&self.node.to_le_bytes())?;
+        w.write_all(&self.depth.to_le_bytes())?;
+        write_bytes(w, &self.bytes)?;
+        Ok(24 + 8 + self.bytes.len() as u64)
+    }
+
+    fn read(r: &mut impl Read) -> io::Result<Option<Self>> {
+        let Some(hash) = read_u64(r)? else {
+            return Ok(None);
+        };
+        let node = read_u64(r)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+        let depth = read_u64(r)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+        let bytes = read_bytes(r)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+        Ok(Some(Self {
+            hash,
+            node,
+            depth,
+            bytes,
+        }))
+    }
+}
+
+/// A file of spilled states sorted by hash, with a sparse index and a Bloom filter
+/// kept in memory, so that looking up an unvisited state rarely touches the disk,
+/// and looking up a visited one reads only a block of the file.
+struct Run {
+    path: PathBuf,
+    reader: BufReader<File>,
+    /// The hash and file offset of every `BLOCK`th entry
+    index: Vec<(u64, u64)>,
+    bloom: Bloom,
+    len: usize,
+}
+
+impl Run {
+    const BLOCK: usize = 64;
+
+    fn write(
+        path: PathBuf,
+        len: usize,
+        entries: impl Iterator<Item = io::Result<Entry>>,
+    ) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        let mut index = vec![];
+        let mut bloom = Bloom::new(len);
+        let mut offset = 0;
+        let mut len = 0;
+        for entry in entries {
+            let entry = entry?;
+            if len % Self::BLOCK == 0 {
+                index.push((entry.hash, offset));
+            }
+            bloom.insert(entry.hash);
+            offset += entry.write(&mut writer)?;
+            len += 1;
+        }
+        writer.flush()?;
+        Ok(Self {
+            reader: BufReader::new(File::open(&path)?),
+            path,
+            index,
+            bloom,
+            len,
+        })
+    }
+
+    /// Merge two runs into one, preferring the newer entry for any state in both.
+    fn merge(path: PathBuf, newer: Run, older: Run) -> io::Result<Self> {
+        let len = newer.len + older.len;
+        let mut newer_entries = newer.entries()?.peekable();
+        let mut older_entries = older.entries()?.peekable();
+        let merged = std::iter::from_fn(|| {
+            let take_newer = match (newer_entries.peek(), older_entries.peek()) {
+                (Some(Ok(n)), Some(Ok(o))) => {
+                    let take_newer = n.hash <= o.hash;
+                    if n.hash == o.hash && n.bytes == o.bytes {
+                        // the older entry is out of date
+                        older_entries.next();
+                    }
+                    take_newer
+                }
+                (Some(Err(_)), _) | (_, None) => true,
+                _ => false,
+            };
+            if take_newer {
+                newer_entries.next()
+            } else {
+                older_entries.next()
+            }
+        });
+        Self::write(path, len, merged)
+    }
+
+    /// Read all entries from the start of the file.
+    fn entries(&self) -> io::Result<impl Iterator<Item = io::Result<Entry>> + use<>> {
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        Ok(std::iter::from_fn(move || {
+            Entry::read(&mut reader).transpose()
+        }))
+    }
+
+    /// Find the newest entry with the given hash whose state matches.
+    fn find(
+        &mut self,
+        hash: u64,
+        matches: impl Fn(&[u8]) -> bool,
+    ) -> io::Result<Option<(NodeIndex, usize)>> {
+        if !self.bloom.contains(hash) {
+            return Ok(None);
+        }
+        // Entries with this hash may begin in the block before the first
+        // whose first entry has this hash
+        let block = self
+            .index
+            .partition_point(|(h, _)| *h < hash)
+            .saturating_sub(1);
+        self.reader.seek(SeekFrom::Start(self.index[block].1))?;
+        while let Some(entry) = Entry::read(&mut self.reader)? {
+            if entry.hash > hash {
+                break;
+            }
+            if entry.hash == hash && matches(&entry.bytes) {
+                return Ok(Some((
+                    NodeIndex::new(entry.node as usize),
+                    entry.depth as usize,
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A Bloom filter over hashes.
+struct Bloom {
+    bits: Vec<u64>,
+    hashes: u32,
+    /// The number of bits set
+    ones: usize,
+}
+
+impl Bloom {
+    /// A filter for the given number of hashes, with about a 1% false positive rate.
+    fn new(len: usize) -> Self {
+        Self::with_bits(len * 10, 7)
+    }
+
+    fn with_bits(num_bits: usize, hashes: u32) -> Self {
+        Self {
+            bits: vec![0; num_bits.div_ceil(64).max(1)],
+            hashes: hashes.max(1),
+            ones: 0,
+        }
+    }
+
+    fn positions(&self, hash: u64) -> impl Iterator<Item = usize> + use<> {
+        let num_bits = self.bits.len() as u64 * 64;
+        let step = hash.rotate_left(32) | 1;
+        (0..self.hashes as u64)
+            .map(move |i| (hash.wrapping_add(i.wrapping_mul(step)) % num_bits) as usize)
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for bit in self.positions(hash) {
+            let word = &mut self.bits[bit / 64];
+            if *word & (1 << (bit % 64)) == 0 {
+                *word |= 1 << (bit % 64);
+                self.ones += 1;
+            }
+        }
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        self.positions(hash)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// The chance that a hash which was never inserted is contained, given the bits set so far.
+    fn false_positive_rate(&self) -> f64 {
+        let full = self.ones as f64 / (self.bits.len() * 64) as f64;
+        full.powi(self.hashes as i32)
+    }
+}
+
+/// A queue of states waiting to be visited, which holds a bounded number in memory
+/// and appends the rest to a file.
+pub(crate) struct Frontier<T> {
+    memory: Vec<T>,
+    disk: Option<FrontierDisk<T>>,
+}
+
+struct FrontierDisk<T> {
+    dir: Arc<SpillDir>,
+    codec: Codec<T>,
+    max_in_memory: usize,
+    file: Option<(PathBuf, BufWriter<File>)>,
+    len: usize,
+}
+
+impl<T> Frontier<T> {
+    /// A queue which is kept entirely in memory.
+    pub fn in_memory() -> Self {
+        Self {
+            memory: vec![],
+            disk: None,
+        }
+    }
+
+    /// A queue which appends to a file in the directory once
+    /// `max_in_memory` items are held in memory.
+    pub fn spilling(dir: Arc<SpillDir>, codec: Codec<T>, max_in_memory: usize) -> Self {
+        Self {
+            memory: vec![],
+            disk: Some(FrontierDisk {
+                dir,
+                codec,
+                max_in_memory: max_in_memory.max(1),
+                file: None,
+                len: 0,
+            }),
+        }
+    }
+
+    /// The items held in memory, which is all of them unless any were spilled.
+    pub fn items_in_memory(&self) -> &[T] {
+        &self.memory
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty() && self.disk.as_ref().is_none_or(|disk| disk.len == 0)
+    }
+
+    pub fn push(&mut self, item: T) {
+        match &mut self.disk {
+            Some(disk) if self.memory.len() >= disk.max_in_memory => {
+                or_panic(disk.push(item));
+            }
+            _ => self.memory.push(item),
+        }
+    }
+
+    /// Take all of the items in the order they were pushed,
+    /// in chunks no bigger than the number held in memory.
+    pub fn into_chunks(self) -> impl Iterator<Item = Vec<T>> {
+        let mut memory = Some(self.memory).filter(|memory| !memory.is_empty());
+        let mut spilled = self.disk.and_then(|disk| or_panic(disk.into_reader()));
+        std::iter::from_fn(move || {
+            if let Some(memory) = memory.take() {
+                return Some(memory);
+            }
+            let (reader, codec, max_in_memory) = spilled.as_mut()?;
+            let mut chunk = vec![];
+            while chunk.len() < *max_in_memory
+                && let Some(bytes) = or_panic(read_bytes(reader))
+            {
+                chunk.push(or_panic((codec.decode)(&bytes)));
+            }
+            if chunk.is_empty() {
+                spilled = None;
+                None
+            } else {
+                Some(chunk)
+            }
+        })
+    }
+}
+
+impl<T> Extend<T> for Frontier<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+impl<T> FrontierDisk<T> {
+    fn push(&mut self, item: T) -> io::Result<()> {
+        let (_, writer) = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let path = self.dir.new_file("frontier");
+                let writer = BufWriter::new(File::create(&path)?);
+                self.file.insert((path, writer))
+            }
+        };
+        write_bytes(writer, &(self.codec.encode)(&item))?;
+        self.len += 1;
+        self.dir.num_spilled.fetch_add(1, SeqCst);
+        Ok(())
+    }
+
+    /// Finish writing, and read the file back.
+    #[allow(clippy::type_complexity)]
+    fn into_reader(mut self) -> io::Result<Option<(SpilledReader, Codec<T>, usize)>> {
+        let Some((path, mut writer)) = self.file.take() else {
+            return Ok(None);
+        };
+        writer.flush()?;
+        let reader = SpilledReader {
+            reader: BufReader::new(File::open(&path)?),
+            path,
+        };
+        Ok(Some((reader, self.codec, self.max_in_memory)))
+    }
+}
+
+/// Reads a spilled file, and removes it when done.
+struct SpilledReader {
+    path: PathBuf,
+    reader: BufReader<File>,
+}
+
+impl Read for SpilledReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl Drop for SpilledReader {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub(crate) fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    w.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    w.write_all(bytes)
+}
+
+/// Read a length followed by that many bytes, or nothing at the end of the file.
+pub(crate) fn read_bytes(r: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let Some(len) = read_u64(r)? else {
+        return Ok(None);
+    };
+    // A corrupt length must not allocate more than there is to read
+    let mut bytes = vec![];
+    r.by_ref().take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some(bytes))
+}
+
+/// Read a number, or nothing at the end of the file.
+pub(crate) fn read_u64(r: &mut impl Read) -> io::Result<Option<u64>> {
+    let mut buf = [0; 8];
+    match r.read_exact(&mut buf) {
+        Ok(()) => Ok(Some(u64::from_le_bytes(buf))),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read a number, which must be there.
+pub(crate) fn number(r: &mut impl Read) -> io::Result<u64> {
+    read_u64(r)?.ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+}
+
+/// Read a length followed by that many bytes, which must be there.
+pub(crate) fn bytes(r: &mut impl Read) -> io::Result<Vec<u8>> {
+    read_bytes(r)?.ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+}
+
+/// There is no way to recover from failing to read back spilled states.
+pub(crate) fn or_panic<T>(result: io::Result<T>) -> T {
+    result.unwrap_or_else(|e| panic!("error while spilling traversal to disk: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codec() -> Codec<u32> {
+        Codec::new(
+            |n: &u32| n.to_le_bytes().to_vec(),
+            |bytes| Ok(u32::from_le_bytes(bytes.try_into().unwrap())),
+        )
+    }
+
+    #[test]
+    fn visited_spills_and_merges() {
+        let dir = SpillDir::create(&std::env::temp_dir()).unwrap();
+        let mut visited = Visited::spilling(dir.clone(), codec(), 10);
+        for n in 0..1000 {
+            assert_eq!(visited.get(&n), None);
+            visited.insert_new(n, (NodeIndex::new(n as usize), 5));
+        }
+        // Shallower visits to spilled states replace them
+        for n in (0..1000).step_by(7) {
+            visited.update(n, (NodeIndex::new(n as usize), 2));
+        }
+        assert_eq!(visited.len(), 1000);
+        let Store::Exact { memory, disk } = &visited.store else {
+            unreachable!()
+        };
+        assert!(memory.len() < 10);
+        assert!(disk.as_ref().unwrap().runs.len() <= 10);
+        for n in 0..1000 {
+            let depth = if n % 7 == 0 { 2 } else { 5 };
+            assert_eq!(visited.get(&n), Some((NodeIndex::new(n as usize), depth)));
+        }
+        assert_eq!(visited.get(&1000), None);
+
+        let path = dir.path.clone();
+        assert!(path.exists());
+        drop(visited);
+        drop(dir);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn frontier_spills_in_order() {
+        let dir = SpillDir::create(&std::env::temp_dir()).unwrap();
+        let mut frontier = Frontier::spilling(dir, codec(), 10);
+        frontier.extend(0..95);
+        assert!(!frontier.is_empty());
+        let chunks = frontier.into_chunks().collect::<Vec<_>>();
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 10));
+        assert_eq!(chunks.concat(), (0..95).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,261 @@
+//! Swarm verification: many small, differently configured searches of one state space.
+
+use std::{
+    collections::HashSet,
+    hash::{BuildHasher, Hash, RandomState},
+};
+
+use itertools::Itertools;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::machine::Cog;
+use crate::Machine;
+
+use super::{
+    violations, Coverage, InvariantViolation, Sharded, Strategy, Traversal, TraversalReport,
+    WalkFailure,
+};
+
+/// The number of states each member of a swarm may visit, unless limited by
+/// [`Traversal::max_states`]
+const DEFAULT_MEMBER_STATES: usize = 100_000;
+
+/// The number of actions in each random walk, unless limited by [`Traversal::max_depth`]
+const DEFAULT_WALK_STEPS: usize = 1_000;
+
+/// The combined findings of a swarm of searches (see [`Traversal::swarm`]).
+#[derive(Debug)]
+pub struct SwarmReport<S, A> {
+    /// The report of each depth-first search in the swarm, in order
+    pub searches: Vec<TraversalReport>,
+    /// Total random walks taken by the members of the swarm which take them
+    pub num_walks: usize,
+    /// Distinct states visited by any member, as mapped (see [`Traversal::map_state`]),
+    /// counted by fingerprint
+    pub num_visited: usize,
+    /// Total iterations taken by the searches, and actions taken by the walks
+    pub total_steps: usize,
+    /// How often each kind of action succeeded and failed, over all searches, if recorded
+    /// (see [`Traversal::coverage`])
+    pub coverage: Option<Coverage>,
+    /// Each invariant violated in a state found by any member, in the order the invariants
+    /// were added, with the shortest path to it found by any member
+    /// (see [`Traversal::check_invariants`])
+    pub violations: Vec<InvariantViolation<S, A>>,
+}
+
+/// What one member of a swarm found
+enum Findings<S, A> {
+    Search(TraversalReport, Vec<InvariantViolation<S, A>>),
+    Walks {
+        num_walks: usize,
+        total_steps: usize,
+        violations: Vec<InvariantViolation<S, A>>,
+    },
+}
+
+/// The fingerprints of the states visited by every member of a swarm,
+/// so that states are counted once however many members visit them
+struct Fingerprints {
+    hasher: RandomState,
+    seen: Sharded<HashSet<u64>>,
+}
+
+impl Fingerprints {
+    fn insert(&self, state: &impl Hash) {
+        let hash = self.hasher.hash_one(state);
+        self.seen.lock(&hash).insert(hash);
+    }
+
+    fn len(&self) -> usize {
+        self.seen.sum(HashSet::len)
+    }
+}
+
+impl<M, S, A> Traversal<M, S, A>
+where
+    M: Machine + Clone,
+    S: Cog + Hash + Eq + 'static,
+    A: Cog + Hash + Eq + 'static,
+{
+    /// Run `members` independent searches on all cores, and combine what they find.
+    /// Every other member is a depth-first search, trying the actions in every state
+    /// in a different random order, and the rest take seeded random walks
+    /// (see [`Traversal::simulate`]). Each member is configured like this traversal,
+    /// so the visitor is called and invariants are checked in every state any of them
+    /// visits. When a [`Traversal::max_depth`] is set, each member also stops at its own
+    /// random depth, between half that depth and that depth, which is the length of
+    /// its walks.
+    ///
+    /// This is for state spaces too large to explore exhaustively: each member has
+    /// a budget, and being different, together they cover much more of the state space
+    /// than any one of them, or than one search with the combined budget, which would only
+    /// ever explore the same corner. Each member visits at most [`Traversal::max_states`]
+    /// states, or 100,000 if that is not set, and a search also stops at any other limit
+    /// set (see [`Traversal::time_limit`]). Only a fingerprint of each state visited is
+    /// kept once a member is done, along with the paths to any violations it found,
+    /// so the memory used grows with the budget of each member rather than with
+    /// the number of members.
+    ///
+    /// The same seed always configures the same members, though with a time limit
+    /// the searches may not get as far each time.
+    pub fn swarm(mut self, members: usize, seed: u64) -> Result<SwarmReport<S, M::Action>, M::Error>
+    where
+        M::Action: Hash + Eq,
+    {
+        let names = self
+            .invariants
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect_vec();
+        let check_deadlock = self.options.check_deadlock;
+        let max_states = *self
+            .options
+            .budget
+            .max_states
+            .get_or_insert(DEFAULT_MEMBER_STATES);
+        let visited = Fingerprints {
+            hasher: RandomState::new(),
+            seen: Sharded::new(rayon::current_num_threads()),
+        };
+
+        let results = (0..members)
+            .into_par_iter()
+            .map(|member| {
+                let member_seed = splitmix64(seed ^ splitmix64(member as u64));
+                let mut traversal = self.clone();
+                traversal.options.max_depth = self.options.max_depth.map(|max| {
+                    let min = max / 2;
+                    min + (splitmix64(member_seed) % (max - min + 1) as u64) as usize
+                });
+                if member % 2 == 1 {
+                    return traversal.walk(member_seed, max_states, &visited);
+                }
+
+                let mut traversal = traversal.strategy(Strategy::Dfs).deterministic(true);
+                traversal.options.shuffle = Some(member_seed);
+                let (traversed, graph, paths) = traversal.traverse_with_paths()?;
+                for state in graph.node_weights() {
+                    visited.insert(state);
+                }
+                let found = violations(&traversed, &graph, &paths, names.clone(), check_deadlock);
+                Ok(Findings::Search(traversed.report, found))
+            })
+            .collect::<Result<Vec<_>, M::Error>>()?;
+
+        let mut report = SwarmReport {
+            searches: vec![],
+            num_walks: 0,
+            num_visited: visited.len(),
+            total_steps: 0,
+            coverage: self.coverage.as_ref().map(|_| Coverage::default()),
+            violations: vec![],
+        };
+        for findings in results {
+            let found = match findings {
+                Findings::Search(search, found) => {
+                    report.total_steps += search.total_steps;
+                    if let (Some(coverage), Some(covered)) =
+                        (&mut report.coverage, &search.coverage)
+                    {
+                        coverage.merge(covered.clone());
+                    }
+                    report.searches.push(search);
+                    found
+                }
+                Findings::Walks {
+                    num_walks,
+                    total_steps,
+                    violations,
+                } => {
+                    report.num_walks += num_walks;
+                    report.total_steps += total_steps;
+                    violations
+                }
+            };
+            for violation in found {
+                match report
+                    .violations
+                    .iter_mut()
+                    .find(|v| v.name == violation.name)
+                {
+                    Some(v) if v.path.len() <= violation.path.len() => {}
+                    Some(v) => *v = violation,
+                    None => report.violations.push(violation),
+                }
+            }
+        }
+        report.violations.sort_by_key(|v| {
+            names
+                .iter()
+                .position(|name| *name == v.name)
+                .unwrap_or(names.len())
+        });
+        Ok(report)
+    }
+
+    /// Take random walks of up to the depth limit, with as many steps between them as
+    /// states may be visited, as one member of a swarm, and turn the walks which failed
+    /// into violations
+    fn walk(
+        self,
+        seed: u64,
+        max_states: usize,
+        visited: &Fingerprints,
+    ) -> Result<Findings<S, M::Action>, M::Error> {
+        let steps = self.options.max_depth.unwrap_or(DEFAULT_WALK_STEPS).max(1);
+        let map_state = self.map_state.clone();
+        let simulation = self.simulate_visiting(max_states.div_ceil(steps), steps, seed, |state| {
+            visited.insert(state)
+        });
+
+        let mut violations = vec![];
+        for walk in simulation.failures {
+            let name = match walk.failure {
+                WalkFailure::Invariant(name) => name,
+                WalkFailure::Deadlock => "deadlock".to_string(),
+                WalkFailure::Error(err) => return Err(err),
+            };
+            // The states along the walk, as mapped but not relabeled under symmetry,
+            // so that each action still leads from one to the next
+            let Some(initial) = map_state(walk.initial) else {
+                continue;
+            };
+            let Some(path) = walk
+                .path
+                .into_iter()
+                .map(|(action, state)| Some((action, map_state(state)?)))
+                .collect()
+            else {
+                continue;
+            };
+            violations.push(InvariantViolation {
+                name,
+                initial,
+                path,
+            });
+        }
+        Ok(Findings::Walks {
+            num_walks: simulation.num_walks,
+            total_steps: simulation.total_steps,
+            violations,
+        })
+    }
+}
+
+/// Shuffle the items in place, the same way every time for the same seed
+pub(super) fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    for i in (1..items.len()).rev() {
+        state = splitmix64(state);
+        items.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+}
+
+/// A fast, well-mixed hash of a number, which serves as a simple random number generator
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
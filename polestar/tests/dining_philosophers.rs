@@ -261,15 +261,14 @@ mod tests {
     impl EvaluatePropositions<Prop> for Transition<Model> {
         fn evaluate(&self, prop: &Prop) -> bool {
             let Transition(s, _, _) = self;
-            let out = match *prop {
+            match *prop {
                 Prop::Eating(p) => s.philosophers[*p].phase == Phase::Eating,
                 Prop::Hungry(p) => s.philosophers[*p].phase == Phase::Hungry,
                 Prop::ShareFork(p, q) => {
                     s.forks.left(p).holder == s.forks.right(q).holder
                         || s.forks.right(p).holder == s.forks.left(q).holder
                 }
-            };
-            out
+            }
         }
     }
 